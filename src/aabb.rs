@@ -1,6 +1,6 @@
 use crate::{interval::Interval, ray::Ray, vec3::Point3};
 
-#[derive(Default)]
+#[derive(Clone, Copy, Default)]
 pub struct Aabb {
 	pub x: Interval,
 	pub y: Interval,
@@ -8,15 +8,44 @@ pub struct Aabb {
 }
 
 impl Aabb {
+	pub const EMPTY: Aabb = Aabb {x: Interval::EMPTY, y: Interval::EMPTY, z: Interval::EMPTY};
+	// Thinnest a box gets, so flat primitives still have a volume to hit
+	const MIN_SIZE: f64 = 0.0001;
+
 	pub fn from_intervals(a: Interval, b: Interval, c: Interval) -> Aabb {
-		Aabb { x: a, y: b, z: c}
+		Aabb { x: a, y: b, z: c}.pad_to_minimums()
 	}
 	pub fn from_points(a: Point3, b: Point3) -> Aabb {
 		Aabb {
 			x: if a[0] <= b[0] {Interval::new(a[0], b[0])} else {Interval::new(b[0], a[0])},
 			y: if a[1] <= b[1] {Interval::new(a[1], b[1])} else {Interval::new(b[1], a[1])},
-			z: if a[2] <= b[0] {Interval::new(a[2], b[2])} else {Interval::new(b[2], a[2])},
+			z: if a[2] <= b[2] {Interval::new(a[2], b[2])} else {Interval::new(b[2], a[2])},
+		}.pad_to_minimums()
+	}
+	// Just the point, not padded, for bounds that have to keep their real extent
+	pub fn from_point(p: Point3) -> Aabb {
+		Aabb { x: Interval::new(p[0], p[0]), y: Interval::new(p[1], p[1]), z: Interval::new(p[2], p[2]) }
+	}
+	// Smallest box enclosing both boxes
+	pub fn from_boxes(a: &Aabb, b: &Aabb) -> Aabb {
+		Aabb {
+			x: Interval::from_intervals(&a.x, &b.x),
+			y: Interval::from_intervals(&a.y, &b.y),
+			z: Interval::from_intervals(&a.z, &b.z),
+		}
+	}
+	// Widens any non-empty axis thinner than MIN_SIZE, leaves empty ones alone
+	fn pad_to_minimums(mut self) -> Aabb {
+		for ax in [&mut self.x, &mut self.y, &mut self.z] {
+			if ax.min <= ax.max && ax.size() < Self::MIN_SIZE {
+				*ax = ax.expand(Self::MIN_SIZE);
+			}
 		}
+		self
+	}
+	// False for boxes that reach infinity, like the one around a plane
+	pub fn is_finite(&self) -> bool {
+		[self.x, self.y, self.z].iter().all(|ax| ax.min.is_finite() && ax.max.is_finite())
 	}
 	pub fn axis_interval(&self, n: usize) -> &Interval {
 		match n {
@@ -25,8 +54,31 @@ impl Aabb {
 			_ => &self.x
 		}
 	}
+	pub fn centroid(&self) -> Point3 {
+		Point3::new(
+			0.5 * (self.x.min + self.x.max),
+			0.5 * (self.y.min + self.y.max),
+			0.5 * (self.z.min + self.z.max),
+		)
+	}
+	pub fn surface_area(&self) -> f64 {
+		let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+		if dx < 0.0 || dy < 0.0 || dz < 0.0 {
+			return 0.0;
+		}
+		2.0 * (dx*dy + dy*dz + dz*dx)
+	}
+	pub fn longest_axis(&self) -> usize {
+		if self.x.size() > self.y.size() {
+			if self.x.size() > self.z.size() {0} else {2}
+		} else if self.y.size() > self.z.size() {1} else {2}
+	}
 
-	pub fn hit(&mut self, r: Ray, mut ray_t: Interval) -> bool {
+	pub fn hit(&self, r: &Ray, ray_t: Interval) -> bool {
+		self.ray_interval(r, ray_t).is_some()
+	}
+	// Part of ray_t the ray spends inside the box
+	pub fn ray_interval(&self, r: &Ray, mut ray_t: Interval) -> Option<Interval> {
 		let ray_orig = r.origin();
 		let ray_dir = r.dir();
 
@@ -46,10 +98,10 @@ impl Aabb {
 			}
 
 			if ray_t.max <= ray_t.min {
-				return false;
+				return None;
 			}
 		}
-		true
+		Some(ray_t)
 	}
 	
-}
\ No newline at end of file
+}
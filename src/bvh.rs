@@ -0,0 +1,468 @@
+use std::{fmt::Display, sync::Arc};
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, hittable_list::HittableList, interval::Interval, ray::Ray, vec3::Point3};
+
+// Relative costs used by the surface area heuristic
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECT_COST: f64 = 1.0;
+// Keeps the traversal stack a fixed size
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplitStrategy {
+	// Split the centroid bounds in half along the longest axis
+	Midpoint,
+	// Split at the median so both halves get the same number of primitives
+	EqualCounts,
+	// Binned surface area heuristic
+	Sah { bins: usize },
+}
+
+impl Default for SplitStrategy {
+	fn default() -> Self {
+		SplitStrategy::Sah { bins: 12 }
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BvhBuilder {
+	pub strategy: SplitStrategy,
+	pub max_leaf_size: usize,
+}
+
+impl Default for BvhBuilder {
+	fn default() -> Self {
+		Self { strategy: SplitStrategy::default(), max_leaf_size: 4 }
+	}
+}
+
+impl BvhBuilder {
+	pub fn new(strategy: SplitStrategy) -> Self {
+		Self { strategy, ..Default::default() }
+	}
+
+	// Builds a hierarchy over primitives given only their bounding boxes
+	pub fn build(&self, boxes: &[Aabb]) -> Bvh {
+		let mut bvh = Bvh { nodes: Vec::new(), indices: (0..boxes.len()).collect() };
+		if !boxes.is_empty() {
+			let centroids: Vec<Point3> = boxes.iter().map(|b| b.centroid()).collect();
+			let mut indices = std::mem::take(&mut bvh.indices);
+			self.build_recursive(&mut bvh.nodes, boxes, &centroids, &mut indices, 0, 0);
+			bvh.indices = indices;
+		}
+		bvh
+	}
+
+	fn build_recursive(&self, nodes: &mut Vec<Node>, boxes: &[Aabb], centroids: &[Point3], indices: &mut [usize], first: usize, depth: usize) -> usize {
+		let bbox = indices.iter().fold(Aabb::EMPTY, |acc, &i| Aabb::from_boxes(&acc, &boxes[i]));
+		let node_index = nodes.len();
+		nodes.push(Node { bbox, kind: NodeKind::Leaf { first, count: indices.len() } });
+
+		let count = indices.len();
+		if count <= 1 || depth + 1 >= MAX_DEPTH {
+			return node_index;
+		}
+
+		// Unpadded, so centroids in one plane or one place leave that axis unsplittable
+		let centroid_bounds = indices.iter().fold(Aabb::EMPTY, |acc, &i| Aabb::from_boxes(&acc, &Aabb::from_point(centroids[i])));
+		let axis = centroid_bounds.longest_axis();
+		let extent = centroid_bounds.axis_interval(axis);
+
+		let mid = if !can_split(extent) {
+			// Every centroid is in the same place, no plane can separate them
+			if count <= self.max_leaf_size {
+				return node_index;
+			}
+			Self::split_equal_counts(centroids, indices, axis)
+		} else {
+			match self.strategy {
+				SplitStrategy::Midpoint => {
+					if count <= self.max_leaf_size {
+						return node_index;
+					}
+					let pmid = 0.5 * (extent.min + extent.max);
+					let mid = partition(indices, |&i| centroids[i][axis] < pmid);
+					if mid == 0 || mid == count {
+						Self::split_equal_counts(centroids, indices, axis)
+					} else {
+						mid
+					}
+				}
+				SplitStrategy::EqualCounts => {
+					if count <= self.max_leaf_size {
+						return node_index;
+					}
+					Self::split_equal_counts(centroids, indices, axis)
+				}
+				SplitStrategy::Sah { bins } => {
+					match self.split_sah(boxes, centroids, indices, &bbox, &centroid_bounds, bins.max(2)) {
+						Some(mid) => mid,
+						None => return node_index,
+					}
+				}
+			}
+		};
+
+		let (left_indices, right_indices) = indices.split_at_mut(mid);
+		let left = self.build_recursive(nodes, boxes, centroids, left_indices, first, depth + 1);
+		let right = self.build_recursive(nodes, boxes, centroids, right_indices, first + mid, depth + 1);
+		nodes[node_index].kind = NodeKind::Interior { left, right, axis };
+		node_index
+	}
+
+	fn split_equal_counts(centroids: &[Point3], indices: &mut [usize], axis: usize) -> usize {
+		let mid = indices.len() / 2;
+		indices.select_nth_unstable_by(mid, |&a, &b| centroids[a][axis].total_cmp(&centroids[b][axis]));
+		mid
+	}
+
+	// Returns the split position, or None when a leaf is cheaper than any split
+	fn split_sah(&self, boxes: &[Aabb], centroids: &[Point3], indices: &mut [usize], bbox: &Aabb, centroid_bounds: &Aabb, bins: usize) -> Option<usize> {
+		let count = indices.len();
+		let mut best: Option<(f64, usize, usize)> = None;
+
+		for (axis, extent) in [centroid_bounds.x, centroid_bounds.y, centroid_bounds.z].iter().enumerate() {
+			if !can_split(extent) {
+				continue;
+			}
+			let bin_of = |c: f64| (((c - extent.min) / extent.size() * bins as f64) as usize).min(bins - 1);
+
+			let mut bin_boxes = vec![Aabb::EMPTY; bins];
+			let mut bin_counts = vec![0usize; bins];
+			for &i in indices.iter() {
+				let b = bin_of(centroids[i][axis]);
+				bin_counts[b] += 1;
+				bin_boxes[b] = Aabb::from_boxes(&bin_boxes[b], &boxes[i]);
+			}
+
+			// Sweep from the right to get the cost of every right-hand side
+			let mut right_area = vec![0.0; bins];
+			let mut right_count = vec![0usize; bins];
+			let mut acc_box = Aabb::EMPTY;
+			let mut acc_count = 0;
+			for b in (1..bins).rev() {
+				acc_box = Aabb::from_boxes(&acc_box, &bin_boxes[b]);
+				acc_count += bin_counts[b];
+				right_area[b] = acc_box.surface_area();
+				right_count[b] = acc_count;
+			}
+
+			let mut acc_box = Aabb::EMPTY;
+			let mut acc_count = 0;
+			for split in 1..bins {
+				acc_box = Aabb::from_boxes(&acc_box, &bin_boxes[split - 1]);
+				acc_count += bin_counts[split - 1];
+				if acc_count == 0 || right_count[split] == 0 {
+					continue;
+				}
+				let cost = acc_box.surface_area() * acc_count as f64 + right_area[split] * right_count[split] as f64;
+				if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+					best = Some((cost, axis, split));
+				}
+			}
+		}
+
+		let parent_area = bbox.surface_area();
+		let leaf_cost = INTERSECT_COST * count as f64;
+		let Some((cost, axis, split)) = best else {
+			return if count <= self.max_leaf_size { None } else { Some(Self::split_equal_counts(centroids, indices, centroid_bounds.longest_axis())) };
+		};
+		let split_cost = if parent_area > 0.0 && parent_area.is_finite() {
+			TRAVERSAL_COST + INTERSECT_COST * cost / parent_area
+		} else {
+			TRAVERSAL_COST + leaf_cost / 2.0
+		};
+		if split_cost >= leaf_cost && count <= self.max_leaf_size {
+			return None;
+		}
+
+		let extent = centroid_bounds.axis_interval(axis);
+		let bin_of = |c: f64| (((c - extent.min) / extent.size() * bins as f64) as usize).min(bins - 1);
+		Some(partition(indices, |&i| bin_of(centroids[i][axis]) < split))
+	}
+}
+
+// False when the centroids along an axis cannot be told apart
+fn can_split(extent: &Interval) -> bool {
+	extent.size() > 0.0 && extent.size().is_finite()
+}
+
+// Moves every element matching the predicate to the front, returns how many matched
+fn partition<T, F: Fn(&T) -> bool>(items: &mut [T], pred: F) -> usize {
+	let mut mid = 0;
+	for i in 0..items.len() {
+		if pred(&items[i]) {
+			items.swap(i, mid);
+			mid += 1;
+		}
+	}
+	mid
+}
+
+#[derive(Clone, Copy)]
+enum NodeKind {
+	Leaf { first: usize, count: usize },
+	Interior { left: usize, right: usize, axis: usize },
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+	bbox: Aabb,
+	kind: NodeKind,
+}
+
+// Flattened hierarchy that only knows primitives by index
+pub struct Bvh {
+	nodes: Vec<Node>,
+	// Primitive indices, leaves refer to ranges of this
+	indices: Vec<usize>,
+}
+
+impl Bvh {
+	pub fn bounding_box(&self) -> Aabb {
+		self.nodes.first().map_or(Aabb::EMPTY, |n| n.bbox)
+	}
+
+	// Calls hit_primitive for every primitive whose leaf the ray reaches.
+	// It returns the t of a hit so the rest of the traversal can be narrowed.
+	pub fn hit<F: FnMut(usize, Interval) -> Option<f64>>(&self, r: &Ray, ray_t: Interval, mut hit_primitive: F) -> bool {
+		if self.nodes.is_empty() {
+			return false;
+		}
+		let mut hit_anything = false;
+		let mut closest_so_far = ray_t.max;
+		let mut stack = [0usize; MAX_DEPTH + 1];
+		let mut stack_len = 1;
+
+		while stack_len > 0 {
+			stack_len -= 1;
+			let node = &self.nodes[stack[stack_len]];
+			if !node.bbox.hit(r, Interval::new(ray_t.min, closest_so_far)) {
+				continue;
+			}
+			match node.kind {
+				NodeKind::Leaf { first, count } => {
+					for &i in &self.indices[first..first + count] {
+						if let Some(t) = hit_primitive(i, Interval::new(ray_t.min, closest_so_far)) {
+							hit_anything = true;
+							closest_so_far = t;
+						}
+					}
+				}
+				NodeKind::Interior { left, right, axis } => {
+					// Visit the nearer child first
+					let (near, far) = if r.dir()[axis] < 0.0 { (right, left) } else { (left, right) };
+					stack[stack_len] = far;
+					stack[stack_len + 1] = near;
+					stack_len += 2;
+				}
+			}
+		}
+		hit_anything
+	}
+
+	pub fn stats(&self) -> BvhStats {
+		let mut stats = BvhStats { min_leaf_size: usize::MAX, ..Default::default() };
+		if self.nodes.is_empty() {
+			stats.min_leaf_size = 0;
+			return stats;
+		}
+		let root_area = self.nodes[0].bbox.surface_area();
+		let mut cost = 0.0;
+		let mut leaf_total = 0;
+		let mut stack = vec![(0usize, 1usize)];
+		while let Some((index, depth)) = stack.pop() {
+			let node = &self.nodes[index];
+			let area = if root_area > 0.0 && root_area.is_finite() { node.bbox.surface_area() / root_area } else { 1.0 };
+			stats.node_count += 1;
+			stats.depth = stats.depth.max(depth);
+			match node.kind {
+				NodeKind::Leaf { count, .. } => {
+					stats.leaf_count += 1;
+					stats.min_leaf_size = stats.min_leaf_size.min(count);
+					stats.max_leaf_size = stats.max_leaf_size.max(count);
+					leaf_total += count;
+					cost += area * INTERSECT_COST * count as f64;
+				}
+				NodeKind::Interior { left, right, .. } => {
+					cost += area * TRAVERSAL_COST;
+					stack.push((left, depth + 1));
+					stack.push((right, depth + 1));
+				}
+			}
+		}
+		stats.mean_leaf_size = leaf_total as f64 / stats.leaf_count as f64;
+		stats.sah_cost = cost;
+		stats
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BvhStats {
+	pub node_count: usize,
+	pub leaf_count: usize,
+	pub depth: usize,
+	pub min_leaf_size: usize,
+	pub max_leaf_size: usize,
+	pub mean_leaf_size: f64,
+	// Expected cost of tracing a ray through the tree, by the surface area heuristic
+	pub sah_cost: f64,
+}
+
+impl Display for BvhStats {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} nodes, {} leaves, depth {}, leaf size {}..{} (mean {:.2}), SAH cost {:.2}",
+			self.node_count, self.leaf_count, self.depth,
+			self.min_leaf_size, self.max_leaf_size, self.mean_leaf_size, self.sah_cost)
+	}
+}
+
+// Bounding volume hierarchy over a set of hittables
+pub struct BvhNode {
+	objects: Vec<Arc<dyn Hittable>>,
+	bvh: Bvh,
+	// Objects without finite bounds, like planes, are tested on every ray
+	unbounded: Vec<Arc<dyn Hittable>>,
+	bbox: Aabb,
+}
+
+impl BvhNode {
+	pub fn new(list: HittableList) -> Self {
+		Self::with_builder(list, &BvhBuilder::default())
+	}
+
+	pub fn with_builder(list: HittableList, builder: &BvhBuilder) -> Self {
+		let bbox = list.bounding_box();
+		let (objects, unbounded): (Vec<_>, Vec<_>) = list.objects.into_iter().partition(|o| o.bounding_box().is_finite());
+		let boxes: Vec<Aabb> = objects.iter().map(|o| o.bounding_box()).collect();
+		let bvh = builder.build(&boxes);
+		BvhNode { objects, bvh, unbounded, bbox }
+	}
+
+	pub fn stats(&self) -> BvhStats {
+		self.bvh.stats()
+	}
+}
+
+impl Hittable for BvhNode {
+	fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+		let mut hit_anything = self.bvh.hit(r, ray_t, |i, t| {
+			if self.objects[i].hit(r, t, rec) { Some(rec.t) } else { None }
+		});
+		for object in &self.unbounded {
+			let closest_so_far = if hit_anything { rec.t } else { ray_t.max };
+			if object.hit(r, Interval::new(ray_t.min, closest_so_far), rec) {
+				hit_anything = true;
+			}
+		}
+		hit_anything
+	}
+
+	fn bounding_box(&self) -> Aabb {
+		self.bbox
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{material::DefaultMaterial, plane::Plane, rng::{self, random_range}, sphere::Sphere, vec3::{unit_vector, Vec3}};
+
+	fn random_point(half: f64) -> Point3 {
+		Point3::new(random_range(-half..half), random_range(-half..half), random_range(-half..half))
+	}
+
+	// Spheres in a cube above a tilted plane, which has no finite box
+	fn world() -> HittableList {
+		rng::seed(7);
+		let mut world = HittableList::default();
+		for _ in 0..300 {
+			world.add(Arc::new(Sphere::stationary(random_point(10.0), random_range(0.1..1.0), Arc::new(DefaultMaterial))));
+		}
+		world.add(Arc::new(Plane::new(Point3::new(0.0, -12.0, 0.0), Vec3::new(0.1, 1.0, 0.2), Arc::new(DefaultMaterial))));
+		world
+	}
+
+	fn hit(world: &dyn Hittable, r: &Ray) -> Option<(f64, Point3)> {
+		let mut rec = HitRecord::default();
+		world.hit(r, Interval::new(0.001, f64::INFINITY), &mut rec).then_some((rec.t, rec.p))
+	}
+
+	#[test]
+	fn strategies_match_linear_list() {
+		let list = world();
+		let strategies = [SplitStrategy::Midpoint, SplitStrategy::EqualCounts, SplitStrategy::Sah { bins: 12 }, SplitStrategy::Sah { bins: 2 }];
+		let trees: Vec<BvhNode> = strategies.iter().map(|&s| {
+			let mut builder = BvhBuilder::new(s);
+			builder.max_leaf_size = 3;
+			BvhNode::with_builder(world(), &builder)
+		}).collect();
+
+		rng::seed(11);
+		let mut hits = 0;
+		for _ in 0..2000 {
+			let r = Ray::new(random_point(15.0), unit_vector(&random_point(1.0)), 0.0);
+			let expected = hit(&list, &r);
+			hits += expected.is_some() as usize;
+			for (tree, strategy) in trees.iter().zip(&strategies) {
+				let actual = hit(tree, &r);
+				assert_eq!(actual.map(|(t, _)| t), expected.map(|(t, _)| t), "{strategy:?}");
+				if let (Some((_, a)), Some((_, e))) = (actual, expected) {
+					assert!((a - e).near_zero(), "{strategy:?}");
+				}
+			}
+		}
+		// Enough of both to mean something
+		assert!(hits > 500 && hits < 1900, "{hits}");
+	}
+
+	#[test]
+	fn unbounded_objects_stay_out_of_the_tree() {
+		let bvh = BvhNode::new(world());
+		assert_eq!((bvh.objects.len(), bvh.unbounded.len()), (300, 1));
+		let stats = bvh.stats();
+		assert_eq!((stats.mean_leaf_size * stats.leaf_count as f64).round() as usize, 300);
+		assert!(!bvh.bounding_box().is_finite());
+
+		// Straight down far outside the spheres, only the plane is there
+		let r = Ray::new(Point3::new(100.0, 0.0, 100.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+		let (_, p) = hit(&bvh, &r).expect("the plane is everywhere");
+		assert!((0.1 * p.x() + (p.y() + 12.0) + 0.2 * p.z()).abs() < 1e-9);
+
+		// A plane hit nearer than any sphere wins over the tree
+		let r = Ray::new(Point3::new(0.0, -11.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+		assert_eq!(hit(&bvh, &r).map(|(t, _)| t), hit(&world(), &r).map(|(t, _)| t));
+	}
+
+	#[test]
+	fn stats() {
+		let empty = BvhBuilder::default().build(&[]);
+		let stats = empty.stats();
+		assert_eq!((stats.node_count, stats.leaf_count, stats.depth, stats.min_leaf_size), (0, 0, 0, 0));
+
+		let unit = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+		let stats = BvhBuilder::default().build(&[unit]).stats();
+		assert_eq!((stats.node_count, stats.leaf_count, stats.depth, stats.min_leaf_size, stats.max_leaf_size), (1, 1, 1, 1, 1));
+		assert_eq!(stats.sah_cost, INTERSECT_COST);
+
+		// Two far apart boxes split into two leaves each half the root area or less
+		let far = Aabb::from_points(Point3::new(10.0, 0.0, 0.0), Point3::new(11.0, 1.0, 1.0));
+		let mut builder = BvhBuilder::new(SplitStrategy::Midpoint);
+		builder.max_leaf_size = 1;
+		let stats = builder.build(&[unit, far]).stats();
+		assert_eq!((stats.node_count, stats.leaf_count, stats.depth, stats.max_leaf_size), (3, 2, 2, 1));
+		let root_area = Aabb::from_boxes(&unit, &far).surface_area();
+		assert!((stats.sah_cost - (TRAVERSAL_COST + 2.0 * INTERSECT_COST * unit.surface_area() / root_area)).abs() < 1e-12);
+
+		let boxes: Vec<Aabb> = (0..100).map(|i| {
+			let p = Point3::new(i as f64, (i % 7) as f64, (i % 3) as f64);
+			Aabb::from_points(p, p + Vec3::new(0.5, 0.5, 0.5))
+		}).collect();
+		for strategy in [SplitStrategy::Midpoint, SplitStrategy::EqualCounts, SplitStrategy::default()] {
+			let stats = BvhBuilder::new(strategy).build(&boxes).stats();
+			assert_eq!(stats.node_count, 2 * stats.leaf_count - 1, "{strategy:?}");
+			assert_eq!((stats.mean_leaf_size * stats.leaf_count as f64).round() as usize, 100, "{strategy:?}");
+			assert!(stats.min_leaf_size >= 1 && stats.max_leaf_size <= 4, "{strategy:?}");
+		}
+	}
+}
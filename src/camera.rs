@@ -1,12 +1,114 @@
-use std::{f64::INFINITY, io::{self, stdout, BufWriter}};
+use std::{sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex}, thread};
 
-use rand::{random, thread_rng, Rng};
+use crate::{color::Color, hittable::{HitRecord, Hittable}, image::{Aovs, Image}, interval::Interval, ray::Ray, rng::{self, random_double, random_range}, sphere::Sphere, texture::Texture, vec3::{cross, random_in_unit_disk, unit_vector, Point3, Vec3}};
+
+// What rays that miss everything see
+#[derive(Clone)]
+pub enum Background {
+	// Blends from bottom to top by the height of the direction
+	Gradient { bottom: Color, top: Color },
+	Solid(Color),
+	// Texture looked up by direction with the same (u, v) as a sphere, for lat-long maps
+	Environment(Arc<dyn Texture>),
+}
+impl Background {
+	pub fn value(&self, dir: &Vec3) -> Color {
+		match self {
+			Background::Gradient { bottom, top } => {
+				let unit_direction = unit_vector(dir);
+				let a = 0.5*(unit_direction.y() + 1.0);
+				(1.0-a)*(*bottom) + a*(*top)
+			}
+			Background::Solid(color) => *color,
+			Background::Environment(tex) => {
+				let unit_direction = unit_vector(dir);
+				let (u, v) = Sphere::get_sphere_uv(&unit_direction);
+				tex.value(u, v, &unit_direction)
+			}
+		}
+	}
+}
+// The sky from the first book
+impl Default for Background {
+	fn default() -> Self {
+		Background::Gradient { bottom: Color::new(1.0, 1.0, 1.0), top: Color::new(0.5, 0.7, 1.0) }
+	}
+}
+
+// How exposure is spread over the time the shutter is open
+#[derive(Clone, Default)]
+pub enum ShutterCurve {
+	// Uniform, like an ideal global shutter
+	#[default]
+	Box,
+	// Opens linearly to full at the middle and closes the same way
+	Triangle,
+	// Relative weights at evenly spaced times from open to close, linear in between,
+	// made with ShutterCurve::custom
+	Custom(ShutterWeights),
+}
+
+// Weights scaled so the area under them is 1, with the area before each one
+#[derive(Clone)]
+pub struct ShutterWeights {
+	weights: Vec<f64>,
+	cdf: Vec<f64>,
+}
+
+impl ShutterCurve {
+	// Negative weights count as zero. Falls back to a box without two weights or any area.
+	pub fn custom(weights: &[f64]) -> Self {
+		let weights: Vec<f64> = weights.iter().map(|w| w.max(0.0)).collect();
+		let total: f64 = weights.windows(2).map(|w| 0.5 * (w[0] + w[1])).sum();
+		if weights.len() < 2 || total <= 0.0 || !total.is_finite() {
+			return ShutterCurve::Box;
+		}
+		let weights: Vec<f64> = weights.iter().map(|w| w / total).collect();
+		let mut cdf = vec![0.0];
+		for w in weights.windows(2) {
+			cdf.push(cdf[cdf.len() - 1] + 0.5 * (w[0] + w[1]));
+		}
+		ShutterCurve::Custom(ShutterWeights { weights, cdf })
+	}
+	// Maps a uniform sample in [0, 1) to the fraction of the way from open to close
+	pub fn sample(&self, xi: f64) -> f64 {
+		match self {
+			ShutterCurve::Box => xi,
+			ShutterCurve::Triangle => {
+				if xi < 0.5 { (0.5 * xi).sqrt() } else { 1.0 - (0.5 * (1.0 - xi)).sqrt() }
+			}
+			ShutterCurve::Custom(curve) => curve.sample(xi),
+		}
+	}
+}
+
+impl ShutterWeights {
+	pub fn weights(&self) -> &[f64] {
+		&self.weights
+	}
+	// Inverts the piecewise quadratic area under the weights
+	fn sample(&self, xi: f64) -> f64 {
+		let segments = self.weights.len() - 1;
+		let k = (self.cdf.partition_point(|&c| c <= xi) - 1).min(segments - 1);
+		let remaining = xi - self.cdf[k];
+		// Solve (w1 - w0) / 2 s^2 + w0 s = remaining for s in the segment
+		let (w0, w1) = (self.weights[k], self.weights[k + 1]);
+		let slope = w1 - w0;
+		let s = if slope.abs() < 1e-12 {
+			if w0 > 0.0 { remaining / w0 } else { 0.5 }
+		} else {
+			(-w0 + (w0 * w0 + 2.0 * slope * remaining).max(0.0).sqrt()) / slope
+		};
+		(k as f64 + s.clamp(0.0, 1.0)) / segments as f64
+	}
+}
 
-use crate::{color::{write_color, Color}, hittable::{HitRecord, Hittable}, interval::Interval, ray::Ray, vec3::{cross, random_in_unit_disk, unit_vector, Point3, Vec3}};
 #[derive(Default)]
 pub struct Camera {
 	pub aspect_ratio: f64,
 	pub image_width: i32,
+	// Exact height in pixels, otherwise it follows from the width and aspect ratio
+	pub fixed_height: Option<i32>,
 	image_height: i32,
 	center: Point3,
 	pixel00_loc: Point3,
@@ -17,6 +119,7 @@ pub struct Camera {
 	pixel_samples_scale: f64,
 
 	pub max_depth: i32,
+	pub background: Background,
 
 	pub fov: f64,
 
@@ -31,8 +134,42 @@ pub struct Camera {
 
 	pub defocus_angle: f64,
 	pub focus_dist: f64,
+
+	// Ray times fall between these, weighted by the curve
+	pub shutter_open: f64,
+	pub shutter_close: f64,
+	pub shutter_curve: ShutterCurve,
 	defocus_dist_u: Vec3,
 	defocus_dist_v: Vec3,
+
+	// Worker threads, 0 uses every available core
+	pub threads: usize,
+	// Side length in pixels of the square tiles handed to workers
+	pub tile_size: i32,
+	pub seed: u64,
+	// Also write depth, normal, albedo and sample count layers
+	pub aovs: bool,
+	// Tiles remaining on stderr, off by default so the library stays silent
+	pub progress: bool,
+}
+
+// What one pixel gathered from all its samples
+#[derive(Clone, Copy, Default)]
+struct PixelResult {
+	color: Color,
+	depth: f64,
+	normal: Vec3,
+	albedo: Color,
+	samples: u32,
+}
+
+// Surface seen by a camera ray
+#[derive(Default)]
+struct FirstHit {
+	hit: bool,
+	distance: f64,
+	normal: Vec3,
+	albedo: Color,
 }
 impl Camera {
 	pub fn new(aspect_ratio: f64, image_width: i32) -> Self {
@@ -40,32 +177,116 @@ impl Camera {
 			aspect_ratio,
 			image_width,
 			samples_per_pixel: 10,
+			max_depth: 10,
 			fov: 90.0,
 			look_at: Point3::new(0.0, 0.0, -1.0),
 			vup: Vec3::new(0.0, 1.0, 0.0),
 			focus_dist: 10.0,
+			shutter_close: 1.0,
+			tile_size: 16,
 			..Default::default()}
 	}
-	pub fn render(&mut self, world: &dyn Hittable) {
+	// Renders the world into linear colors, writing it out is up to the caller
+	pub fn render_to_buffer(&mut self, world: &dyn Hittable) -> Image {
 		self.initialize();
-        let mut writer = BufWriter::new(io::stdout());
-
-		println!("P3\n{} {}\n255", self.image_width, self.image_height);
-    	for j in 0..=self.image_height {
-        	eprint!("\rScanlines remaining: {} ", self.image_height-j);
-        	for i in 0..self.image_width {
-            	let mut pixel_color = Color::default();
-				for _ in 0..self.samples_per_pixel {
-					let r = self.get_ray(i, j);
-					pixel_color += Self::ray_color(&r, self.max_depth, world);
-				}
-            	write_color(&mut writer, self.pixel_samples_scale * pixel_color);
-        }
-    }
-    eprintln!("\rDone.                 \n");
+		let results = self.render_tiles(world);
+		let aovs = self.aovs.then(|| Aovs {
+			depth: results.iter().map(|p| p.depth).collect(),
+			normal: results.iter().map(|p| p.normal).collect(),
+			albedo: results.iter().map(|p| p.albedo).collect(),
+			samples: results.iter().map(|p| p.samples).collect(),
+		});
+		Image {
+			width: self.image_width.max(0) as usize,
+			height: self.image_height.max(0) as usize,
+			pixels: results.iter().map(|p| p.color).collect(),
+			aovs,
+		}
+	}
+	// Renders the image in tiles across worker threads, rows top to bottom
+	fn render_tiles(&self, world: &dyn Hittable) -> Vec<PixelResult> {
+		let width = self.image_width.max(0);
+		let height = self.image_height.max(0);
+		let tile_size = self.tile_size.max(1);
+		let tiles_x = (width + tile_size - 1) / tile_size;
+		let tiles_y = (height + tile_size - 1) / tile_size;
+		let tile_count = (tiles_x * tiles_y) as usize;
+
+		let threads = match self.threads {
+			0 => thread::available_parallelism().map_or(1, |n| n.get()),
+			n => n,
+		};
+		let next_tile = AtomicUsize::new(0);
+		let tiles_done = AtomicUsize::new(0);
+		let pixels = Mutex::new(vec![PixelResult::default(); (width * height) as usize]);
+
+		thread::scope(|s| {
+			for _ in 0..threads.min(tile_count) {
+				s.spawn(|| loop {
+					let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+					if tile >= tile_count {
+						break;
+					}
+					let x0 = (tile as i32 % tiles_x) * tile_size;
+					let y0 = (tile as i32 / tiles_x) * tile_size;
+					let x1 = (x0 + tile_size).min(width);
+					let y1 = (y0 + tile_size).min(height);
+
+					let mut block = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+					for j in y0..y1 {
+						for i in x0..x1 {
+							block.push(self.render_pixel(i, j, world));
+						}
+					}
+
+					let mut pixels = pixels.lock().unwrap();
+					for (row, j) in (y0..y1).enumerate() {
+						let start = (j * width + x0) as usize;
+						let len = (x1 - x0) as usize;
+						pixels[start..start + len].copy_from_slice(&block[row * len..(row + 1) * len]);
+					}
+					let done = tiles_done.fetch_add(1, Ordering::Relaxed) + 1;
+					if self.progress {
+						eprint!("\rTiles remaining: {} ", tile_count - done);
+					}
+				});
+			}
+		});
+		if self.progress {
+			eprintln!("\rDone.                 \n");
+		}
+
+		pixels.into_inner().unwrap()
+	}
+	fn render_pixel(&self, i: i32, j: i32, world: &dyn Hittable) -> PixelResult {
+		rng::seed_pixel(self.seed, i, j);
+		let mut pixel_color = Color::default();
+		let mut depth = 0.0;
+		let mut normal = Vec3::default();
+		let mut albedo = Color::default();
+		let mut hits = 0;
+		for _ in 0..self.samples_per_pixel {
+			let r = self.get_ray(i, j);
+			let mut first_hit = FirstHit::default();
+			pixel_color += self.ray_color(&r, self.max_depth, world, Some(&mut first_hit));
+			if first_hit.hit {
+				hits += 1;
+				depth += first_hit.distance;
+				normal += first_hit.normal;
+				albedo += first_hit.albedo;
+			}
+		}
+		PixelResult {
+			color: self.pixel_samples_scale * pixel_color,
+			depth: if hits > 0 { depth / hits as f64 } else { f64::INFINITY },
+			normal: if normal.near_zero() { normal } else { unit_vector(&normal) },
+			albedo: self.pixel_samples_scale * albedo,
+			samples: self.samples_per_pixel.max(0) as u32,
+		}
 	}
 	fn initialize(&mut self) {
-		self.image_height = ((self.image_width as f64/self.aspect_ratio) as i32).max(0);
+		assert!(self.shutter_open <= self.shutter_close, "camera shutter has to close after it opens");
+		self.image_height = self.fixed_height.unwrap_or((self.image_width as f64/self.aspect_ratio) as i32).max(0);
 
 		self.pixel_samples_scale = 1.0 / self.samples_per_pixel as f64;
 
@@ -92,41 +313,84 @@ impl Camera {
 		self.defocus_dist_u = self.u * defocus_radius;
 		self.defocus_dist_v = self.v * defocus_radius;
 	}
-	fn ray_color(r: &Ray, depth: i32, world: &dyn Hittable) -> Color {
+	fn ray_color(&self, r: &Ray, depth: i32, world: &dyn Hittable, first_hit: Option<&mut FirstHit>) -> Color {
 		if depth <= 0 {
 			return Color::default();
 		}
 		let mut rec = HitRecord::default();
-		if world.hit(r, Interval::new(0.001, INFINITY), &mut rec) {
-			let mut scattered = Ray::default();
-			let mut attenuation = Color::default();
-			if rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
-				return attenuation * Self::ray_color(&scattered, depth-1, world);
-			}
-			return Color::default();
+		if !world.hit(r, Interval::new(0.001, f64::INFINITY), &mut rec) {
+			return self.background.value(r.dir());
+		}
 
+		let mut scattered = Ray::default();
+		let mut attenuation = Color::default();
+		let scatters = rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered);
+		let emitted = rec.mat.emitted(&rec);
+		if let Some(first_hit) = first_hit {
+			*first_hit = FirstHit { hit: true, distance: rec.t * r.dir().length(), normal: rec.normal, albedo: attenuation };
+		}
+		if !scatters {
+			return emitted;
 		}
-		let unit_direction = unit_vector(r.dir());
-    	let a = 0.5*(unit_direction.y() + 1.0);
-    	(1.0-a)*Color::new(1.0, 1.0, 1.0) + a*Color::new(0.5, 0.7, 1.0)
+		emitted + attenuation * self.ray_color(&scattered, depth-1, world, None)
 	}
-	fn get_ray(&mut self, i: i32, j: i32) -> Ray {
+	fn get_ray(&self, i: i32, j: i32) -> Ray {
 		let offset = Self::sample_square();
 		let pixel_sample = self.pixel00_loc
 			+ ((i as f64 + offset.x()) * self.pixel_delta_u)
 			+ ((j as f64 + offset.y()) * self.pixel_delta_v);
 		let ray_origin = if self.defocus_angle <= 0.0 {self.center} else {self.defocus_disk_sample()};
 		let ray_direction = pixel_sample - ray_origin;
-		let ray_time: f64 = random();
+		let ray_time = self.shutter_open + (self.shutter_close - self.shutter_open) * self.shutter_curve.sample(random_double());
 
 		Ray::new(ray_origin, ray_direction, ray_time)
 	}
 	fn sample_square() -> Vec3 {
-		let mut rng = thread_rng();
-		Vec3::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5), 0.0)
+		Vec3::new(random_range(-0.5..0.5), random_range(-0.5..0.5), 0.0)
 	}
 	fn defocus_disk_sample(&self) -> Point3 {
 		let p = random_in_unit_disk();
 		self.center + (p[0] * self.defocus_dist_u) + (p[1] * self.defocus_dist_v)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{hittable_list::HittableList, material::{Lambertian, Metal}, sphere::Sphere};
+
+	#[test]
+	#[should_panic(expected = "camera shutter has to close after it opens")]
+	fn shutter_closing_first() {
+		let mut cam = Camera::new(1.0, 2);
+		cam.shutter_open = 2.0;
+		cam.render_to_buffer(&HittableList::default());
+	}
+
+	// Tiles are seeded by pixel, so the thread count can't change the picture
+	#[test]
+	fn threads_render_the_same() {
+		let mut world = HittableList::default();
+		world.add(Arc::new(Sphere::stationary(Point3::new(0.0, -100.5, -1.0), 100.0, Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0))))));
+		world.add(Arc::new(Sphere::stationary(Point3::new(0.0, 0.0, -1.2), 0.5, Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5))))));
+		world.add(Arc::new(Sphere::stationary(Point3::new(1.0, 0.0, -1.0), 0.5, Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.3)))));
+
+		let render = |threads| {
+			let mut cam = Camera::new(16.0 / 9.0, 37);
+			cam.samples_per_pixel = 4;
+			// Smaller than the image and not dividing it, so tiles land across threads
+			cam.tile_size = 5;
+			cam.threads = threads;
+			cam.seed = 3;
+				cam.aovs = true;
+			cam.render_to_buffer(&world)
+		};
+		let (one, four) = (render(1), render(4));
+		assert_eq!((one.width, one.height), (37, 20));
+		assert_eq!((four.width, four.height), (37, 20));
+		assert!(one.pixels.iter().zip(&four.pixels).all(|(a, b)| a.x() == b.x() && a.y() == b.y() && a.z() == b.z()));
+		let (a, b) = (one.aovs.unwrap(), four.aovs.unwrap());
+		assert!(a.depth.iter().zip(&b.depth).all(|(x, y)| x == y || (x.is_infinite() && y.is_infinite())));
+		assert_eq!(a.samples, b.samples);
+	}
+}
@@ -0,0 +1,197 @@
+use std::{fmt::Display, str::FromStr};
+
+use ray_tracing::{bvh::SplitStrategy, tonemap::{ToneMapOperator, TransferFunction}};
+
+// Brightness extended-reinhard maps to 1 when --tonemap doesn't give one
+const DEFAULT_WHITE_POINT: f64 = 4.0;
+
+// Command line options, anything left as None keeps what the scene sets
+pub struct Options {
+	pub scene: String,
+	pub output: Option<String>,
+	pub width: Option<i32>,
+	pub height: Option<i32>,
+	pub samples_per_pixel: Option<i32>,
+	pub max_depth: Option<i32>,
+	// Shutter open and close times, for frames of a sequence
+	pub shutter: Option<(f64, f64)>,
+	pub threads: usize,
+	pub seed: u64,
+	pub quiet: bool,
+	pub aovs: bool,
+	pub bvh: SplitStrategy,
+	pub exposure: f64,
+	pub tonemap: ToneMapOperator,
+	pub transfer: TransferFunction,
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Self {
+			scene: "random_spheres".to_string(),
+			output: None,
+			width: None,
+			height: None,
+			samples_per_pixel: None,
+			max_depth: None,
+			shutter: None,
+			threads: 0,
+			seed: 0,
+			quiet: false,
+			aovs: false,
+			bvh: SplitStrategy::default(),
+			exposure: 0.0,
+			tonemap: ToneMapOperator::Clamp,
+			transfer: TransferFunction::Gamma2,
+		}
+	}
+}
+
+pub enum Command {
+	Render(Options),
+	Help,
+}
+
+pub fn usage(builtin_scenes: &[&str]) -> String {
+	let mut text = String::from("\
+Usage: ray_tracing [options]
+
+Options:
+  -s, --scene <file|name>    Scene file (.toml) or built-in scene [default: random_spheres]
+  -o, --output <path>        Output image, format from the extension: png, ppm, pfm, exr
+                             Writes a text PPM to stdout when left out
+  -w, --width <px>           Image width
+      --height <px>          Image height, changes the aspect ratio when given with --width
+  -n, --samples <n>          Samples per pixel
+  -d, --max-depth <n>        Maximum number of bounces
+      --shutter <open:close> Times the shutter is open between [default: 0:1]
+                             Moving spheres only move from 0 to 1, other times need
+                             `motion` keyframes
+  -j, --threads <n>          Worker threads, 0 uses every core [default: 0]
+      --seed <n>             Seed for the scene and the renderer [default: 0]
+  -q, --quiet                No progress or statistics on stderr
+      --aovs                 Write depth, normal, albedo and sample layers (exr only)
+      --bvh <strategy>       sah, midpoint or equal-counts [default: sah]
+      --exposure <stops>     Exposure adjustment for 8-bit outputs [default: 0]
+      --tonemap <operator>   clamp, reinhard, extended-reinhard[:<white>], aces or agx [default: clamp]
+                             The white point is the brightness that maps to 1 [default: 4]
+      --transfer <function>  gamma2 or srgb [default: gamma2]
+  -h, --help                 Print this help
+
+Built-in scenes (earth and final_scene need images/earthmap.jpg):
+");
+	for name in builtin_scenes {
+		text.push_str(&format!("  {name}\n"));
+	}
+	text
+}
+
+pub fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+	let mut options = Options::default();
+
+	while let Some(arg) = args.next() {
+		// Accept both `--flag value` and `--flag=value`
+		let (flag, inline_value) = match arg.split_once('=') {
+			Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+			_ => (arg.clone(), None),
+		};
+		let mut value = || inline_value.clone().or_else(|| args.next()).ok_or_else(|| format!("missing value for `{flag}`"));
+
+		match flag.as_str() {
+			"-h" | "--help" => return Ok(Command::Help),
+			"-s" | "--scene" => options.scene = value()?,
+			"-o" | "--output" => options.output = Some(value()?),
+			"-w" | "--width" => options.width = Some(parse_number(&flag, &value()?)?),
+			"--height" => options.height = Some(parse_number(&flag, &value()?)?),
+			"-n" | "--samples" => options.samples_per_pixel = Some(parse_number(&flag, &value()?)?),
+			"-d" | "--max-depth" => options.max_depth = Some(parse_number(&flag, &value()?)?),
+			"--shutter" => {
+				let value = value()?;
+				let (open, close) = value.split_once(':').ok_or_else(|| format!("expected `<open>:<close>` for `{flag}`, got `{value}`"))?;
+				let (open, close): (f64, f64) = (parse_number(&flag, open)?, parse_number(&flag, close)?);
+				if close < open {
+					return Err(format!("`{flag}` has to close after it opens"));
+				}
+				options.shutter = Some((open, close));
+			}
+			"-j" | "--threads" => options.threads = parse_number(&flag, &value()?)?,
+			"--seed" => options.seed = parse_number(&flag, &value()?)?,
+			"-q" | "--quiet" => options.quiet = true,
+			"--aovs" => options.aovs = true,
+			"--bvh" => {
+				options.bvh = match value()?.as_str() {
+					"sah" => SplitStrategy::default(),
+					"midpoint" => SplitStrategy::Midpoint,
+					"equal-counts" => SplitStrategy::EqualCounts,
+					other => return Err(format!("unknown BVH strategy `{other}`")),
+				}
+			}
+			"--exposure" => options.exposure = parse_number(&flag, &value()?)?,
+			"--tonemap" => {
+				let value = value()?;
+				// Only extended-reinhard takes a parameter, the white point
+				let (name, white) = match value.split_once(':') {
+					Some((name, white)) => (name, Some(parse_number::<f64>(&flag, white)?)),
+					None => (value.as_str(), None),
+				};
+				if white.is_some() && name != "extended-reinhard" {
+					return Err(format!("`{name}` takes no white point"));
+				}
+				options.tonemap = match name {
+					"clamp" => ToneMapOperator::Clamp,
+					"reinhard" => ToneMapOperator::Reinhard,
+					"extended-reinhard" => match white.unwrap_or(DEFAULT_WHITE_POINT) {
+						white if white > 0.0 && white.is_finite() => ToneMapOperator::ExtendedReinhard { white },
+						_ => return Err(format!("the white point for `{flag}` has to be positive")),
+					},
+					"aces" => ToneMapOperator::Aces,
+					"agx" => ToneMapOperator::Agx,
+					other => return Err(format!("unknown tone mapping operator `{other}`")),
+				}
+			}
+			"--transfer" => {
+				options.transfer = match value()?.as_str() {
+					"gamma2" => TransferFunction::Gamma2,
+					"srgb" => TransferFunction::Srgb,
+					other => return Err(format!("unknown transfer function `{other}`")),
+				}
+			}
+			_ => return Err(format!("unknown argument `{arg}`")),
+		}
+	}
+
+	for (flag, value) in [("--width", options.width), ("--height", options.height), ("--samples", options.samples_per_pixel)] {
+		if value.is_some_and(|v| v <= 0) {
+			return Err(format!("`{flag}` has to be at least 1"));
+		}
+	}
+	Ok(Command::Render(options))
+}
+
+fn parse_number<T: FromStr>(flag: &str, value: &str) -> Result<T, String>
+where
+	T::Err: Display,
+{
+	value.parse().map_err(|e| format!("invalid value `{value}` for `{flag}`: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tonemap(value: &str) -> Result<ToneMapOperator, String> {
+		match parse_args(["--tonemap".to_string(), value.to_string()].into_iter())? {
+			Command::Render(options) => Ok(options.tonemap),
+			Command::Help => panic!("expected options"),
+		}
+	}
+
+	#[test]
+	fn white_point() {
+		assert_eq!(tonemap("extended-reinhard"), Ok(ToneMapOperator::ExtendedReinhard { white: DEFAULT_WHITE_POINT }));
+		assert_eq!(tonemap("extended-reinhard:2.5"), Ok(ToneMapOperator::ExtendedReinhard { white: 2.5 }));
+		assert_eq!(tonemap("extended-reinhard:0"), Err("the white point for `--tonemap` has to be positive".to_string()));
+		assert!(tonemap("extended-reinhard:bright").is_err());
+		assert_eq!(tonemap("reinhard:2"), Err("`reinhard` takes no white point".to_string()));
+	}
+}
@@ -1,24 +1,15 @@
-use std::io::Write;
-
 use crate::{interval::Interval, vec3::Vec3};
 
 pub type Color = Vec3;
 
-pub fn write_color<W: Write>(mut out: W, pixel_color: Color) {
-    let r = pixel_color.x();
-    let g = pixel_color.y();
-    let b = pixel_color.z();
-
-    let r = linear_to_gamma(r);
-    let g = linear_to_gamma(g);
-    let b = linear_to_gamma(b);
-
+// Clamps an already encoded color and quantizes it to 8 bits per channel
+pub fn quantize(encoded: Color) -> [u8; 3] {
     let intensity = Interval::new(0.0, 0.999);
-    let rbyte = (256.0 * intensity.clamp(r)) as i32;
-    let gbyte = (256.0 * intensity.clamp(g)) as i32;
-    let bbyte = (256.0 * intensity.clamp(b)) as i32;
-    out.write_all(format!("{rbyte} {gbyte} {bbyte}\n").as_bytes())
-        .unwrap();
+    [
+        (256.0 * intensity.clamp(encoded.x())) as u8,
+        (256.0 * intensity.clamp(encoded.y())) as u8,
+        (256.0 * intensity.clamp(encoded.z())) as u8,
+    ]
 }
 pub fn linear_to_gamma(linear_component: f64) -> f64 {
     if linear_component > 0.0 {
@@ -26,4 +17,21 @@ pub fn linear_to_gamma(linear_component: f64) -> f64 {
     }
     0.0
 }
-
+// Piecewise sRGB transfer function
+pub fn linear_to_srgb(linear_component: f64) -> f64 {
+    if linear_component <= 0.0 {
+        return 0.0;
+    }
+    if linear_component <= 0.0031308 {
+        return 12.92 * linear_component;
+    }
+    1.055 * linear_component.powf(1.0 / 2.4) - 0.055
+}
+// Inverse of linear_to_srgb, for 8-bit textures and vertex colors
+pub fn srgb_to_linear(encoded: f64) -> f64 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
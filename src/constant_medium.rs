@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use crate::{aabb::Aabb, color::Color, hittable::{HitRecord, Hittable}, interval::Interval, material::{Isotropic, Material}, ray::Ray, rng::random_double, texture::Texture, vec3::Vec3};
+
+// Fog or smoke of even density filling a boundary, which has to be closed and convex
+pub struct ConstantMedium {
+	boundary: Arc<dyn Hittable>,
+	neg_inv_density: f64,
+	phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+	pub fn new(boundary: Arc<dyn Hittable>, density: f64, tex: Arc<dyn Texture>) -> Self {
+		Self::with_phase_function(boundary, density, Arc::new(Isotropic::from_texture(tex)))
+	}
+	pub fn from_color(boundary: Arc<dyn Hittable>, density: f64, albedo: Color) -> Self {
+		Self::with_phase_function(boundary, density, Arc::new(Isotropic::new(albedo)))
+	}
+	// Any material decides what happens where the ray scatters, usually Isotropic
+	pub fn with_phase_function(boundary: Arc<dyn Hittable>, density: f64, phase_function: Arc<dyn Material>) -> Self {
+		ConstantMedium { boundary, neg_inv_density: -1.0 / density, phase_function }
+	}
+}
+
+impl Hittable for ConstantMedium {
+	fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+		// Where the ray enters and leaves the boundary, even if that's behind it
+		let mut rec1 = HitRecord::default();
+		let mut rec2 = HitRecord::default();
+		if !self.boundary.hit(r, Interval::UNIVERSE, &mut rec1) {
+			return false;
+		}
+		if !self.boundary.hit(r, Interval::new(rec1.t + 0.0001, f64::INFINITY), &mut rec2) {
+			return false;
+		}
+
+		rec1.t = rec1.t.max(ray_t.min);
+		rec2.t = rec2.t.min(ray_t.max);
+		if rec1.t >= rec2.t {
+			return false;
+		}
+		rec1.t = rec1.t.max(0.0);
+
+		// Exponentially distributed distance to the next particle
+		let ray_length = r.dir().length();
+		let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
+		let hit_distance = self.neg_inv_density * random_double().ln();
+		if hit_distance > distance_inside_boundary {
+			return false;
+		}
+
+		rec.t = rec1.t + hit_distance / ray_length;
+		rec.p = r.at(rec.t);
+		// Neither means anything inside a volume
+		rec.normal = Vec3::new(1.0, 0.0, 0.0);
+		rec.front_face = true;
+		rec.u = 0.0;
+		rec.v = 0.0;
+		rec.vertex_color = None;
+		rec.mat = self.phase_function.clone();
+		true
+	}
+
+	fn bounding_box(&self) -> Aabb {
+		self.boundary.bounding_box()
+	}
+}
@@ -0,0 +1,208 @@
+use std::sync::Arc;
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable, Span}, interval::Interval, ray::Ray};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CsgOp {
+	Union,
+	Intersection,
+	// The first object with the second cut out of it
+	Difference,
+}
+
+impl CsgOp {
+	fn inside(self, in_a: bool, in_b: bool) -> bool {
+		match self {
+			CsgOp::Union => in_a || in_b,
+			CsgOp::Intersection => in_a && in_b,
+			CsgOp::Difference => in_a && !in_b,
+		}
+	}
+}
+
+// Two closed objects combined into one. Surfaces keep the material of the object they
+// come from, so a cut shows the material of what cut it.
+pub struct Csg {
+	op: CsgOp,
+	a: Arc<dyn Hittable>,
+	b: Arc<dyn Hittable>,
+	bbox: Aabb,
+}
+
+impl Csg {
+	pub fn new(op: CsgOp, a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Self {
+		let (box_a, box_b) = (a.bounding_box(), b.bounding_box());
+		let bbox = match op {
+			CsgOp::Union => Aabb::from_boxes(&box_a, &box_b),
+			CsgOp::Intersection => {
+				let overlap = |x: &Interval, y: &Interval| Interval::new(x.min.max(y.min), x.max.min(y.max));
+				let bbox = Aabb { x: overlap(&box_a.x, &box_b.x), y: overlap(&box_a.y, &box_b.y), z: overlap(&box_a.z, &box_b.z) };
+				if [bbox.x, bbox.y, bbox.z].iter().any(|ax| ax.min > ax.max) { Aabb::EMPTY } else { bbox }
+			}
+			CsgOp::Difference => box_a,
+		};
+		Csg { op, a, b, bbox }
+	}
+	pub fn union(a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Self {
+		Self::new(CsgOp::Union, a, b)
+	}
+	pub fn intersection(a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Self {
+		Self::new(CsgOp::Intersection, a, b)
+	}
+	pub fn difference(a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Self {
+		Self::new(CsgOp::Difference, a, b)
+	}
+	pub fn op(&self) -> CsgOp {
+		self.op
+	}
+}
+
+impl Hittable for Csg {
+	fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+		// Spans only have open ends outside ray_t, so the first finite side is the hit
+		let first = self.spans(r, ray_t).into_iter()
+			.flat_map(|span| [span.enter, span.exit])
+			.find(|side| side.t.is_finite());
+		match first {
+			Some(side) => {
+				*rec = side;
+				true
+			}
+			None => false,
+		}
+	}
+
+	fn bounding_box(&self) -> Aabb {
+		self.bbox
+	}
+
+	fn spans(&self, r: &Ray, ray_t: Interval) -> Vec<Span> {
+		if !self.bbox.hit(r, ray_t) {
+			return Vec::new();
+		}
+		let spans_a = self.a.spans(r, ray_t);
+		if spans_a.is_empty() && self.op != CsgOp::Union {
+			return Vec::new();
+		}
+		combine(self.op, spans_a, self.b.spans(r, ray_t))
+	}
+}
+
+// Walks the sides of both span lists in order, keeping the ones where the result
+// changes between inside and outside
+fn combine(op: CsgOp, spans_a: Vec<Span>, spans_b: Vec<Span>) -> Vec<Span> {
+	let (mut in_a, mut in_b) = (false, false);
+	// (side, from a, entering)
+	let mut sides = Vec::new();
+	for (spans, from_a) in [(spans_a, true), (spans_b, false)] {
+		for span in spans {
+			if span.enter.t.is_finite() {
+				sides.push((span.enter, from_a, true));
+			} else if from_a {
+				in_a = true;
+			} else {
+				in_b = true;
+			}
+			if span.exit.t.is_finite() {
+				sides.push((span.exit, from_a, false));
+			}
+		}
+	}
+	sides.sort_by(|x, y| x.0.t.total_cmp(&y.0.t));
+
+	let mut inside = op.inside(in_a, in_b);
+	let mut enter = inside.then(|| Span::open_end(f64::NEG_INFINITY));
+	let mut result = Vec::new();
+	for (mut side, from_a, entering) in sides {
+		if from_a { in_a = entering } else { in_b = entering }
+		if op.inside(in_a, in_b) == inside {
+			continue;
+		}
+		inside = !inside;
+		// The normal keeps facing the ray, only which side is outside changes. Entering
+		// a subtracted object leaves the result, so refraction sees the right side.
+		side.front_face = inside;
+		if inside {
+			enter = Some(side);
+		} else if let Some(enter) = enter.take() {
+			result.push(Span { enter, exit: side });
+		}
+	}
+	if let Some(enter) = enter {
+		result.push(Span { enter, exit: Span::open_end(f64::INFINITY) });
+	}
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{material::{DefaultMaterial, Material}, sphere::Sphere, vec3::{dot, Point3, Vec3}};
+
+	// A shell from radius 1 to 2, and the material of the cut
+	fn shell() -> (Csg, Arc<dyn Material>) {
+		let cut: Arc<dyn Material> = Arc::new(DefaultMaterial);
+		let outer = Arc::new(Sphere::stationary(Point3::new(0.0, 0.0, 0.0), 2.0, Arc::new(DefaultMaterial)));
+		let inner = Arc::new(Sphere::stationary(Point3::new(0.0, 0.0, 0.0), 1.0, cut.clone()));
+		(Csg::difference(outer, inner), cut)
+	}
+
+	fn down_z(z: f64) -> Ray {
+		Ray::new(Point3::new(0.0, 0.0, z), Vec3::new(0.0, 0.0, -1.0), 0.0)
+	}
+
+	fn ends(spans: &[Span]) -> Vec<(f64, f64)> {
+		spans.iter().map(|s| (s.enter.t, s.exit.t)).collect()
+	}
+
+	fn assert_ends(spans: &[Span], expected: &[(f64, f64)]) {
+		let actual = ends(spans);
+		assert_eq!(actual.len(), expected.len(), "{actual:?}");
+		for (a, e) in actual.iter().zip(expected) {
+			assert!((a.0 - e.0).abs() < 1e-9 || a.0 == e.0, "{actual:?}");
+			assert!((a.1 - e.1).abs() < 1e-9 || a.1 == e.1, "{actual:?}");
+		}
+	}
+
+	#[test]
+	fn sphere_minus_sphere() {
+		let (shell, cut) = shell();
+		let r = down_z(5.0);
+		let spans = shell.spans(&r, Interval::new(0.001, f64::INFINITY));
+		assert_ends(&spans, &[(3.0, 4.0), (6.0, 7.0)]);
+
+		// Leaving through the surface of the cut
+		let inner = &spans[0].exit;
+		assert!(Arc::ptr_eq(&inner.mat, &cut));
+		assert!(!inner.front_face);
+		assert!(dot(inner.normal, *r.dir()) < 0.0);
+		assert!(spans[1].enter.front_face);
+		assert!(dot(spans[1].enter.normal, *r.dir()) < 0.0);
+
+		let mut rec = HitRecord::default();
+		assert!(shell.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+		assert!((rec.t - 3.0).abs() < 1e-9 && rec.front_face);
+	}
+
+	#[test]
+	fn ray_starting_inside() {
+		let (shell, _) = shell();
+		let ray_t = Interval::new(0.001, f64::INFINITY);
+
+		// In the wall of the shell, so the first span has no entry
+		let r = down_z(1.5);
+		let spans = shell.spans(&r, ray_t);
+		assert_ends(&spans, &[(f64::NEG_INFINITY, 0.5), (2.5, 3.5)]);
+		let mut rec = HitRecord::default();
+		assert!(shell.hit(&r, ray_t, &mut rec));
+		assert!((rec.t - 0.5).abs() < 1e-9 && !rec.front_face);
+		assert!(dot(rec.normal, *r.dir()) < 0.0);
+
+		// In the hole, inside both spheres and outside the result
+		let r = down_z(0.0);
+		let spans = shell.spans(&r, ray_t);
+		assert_ends(&spans, &[(1.0, 2.0)]);
+		assert!(spans[0].enter.front_face);
+		assert!(dot(spans[0].enter.normal, *r.dir()) < 0.0);
+	}
+}
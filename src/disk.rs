@@ -0,0 +1,71 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, ray::Ray, vec3::{dot, orthonormal_basis, unit_vector, Point3, Vec3}};
+
+// Flat circle facing along its normal
+pub struct Disk {
+	center: Point3,
+	normal: Vec3,
+	radius: f64,
+	// In-plane axes that the angle in u is measured against
+	tangent: Vec3,
+	bitangent: Vec3,
+	mat: Arc<dyn Material>,
+	bbox: Aabb,
+}
+
+impl Disk {
+	pub fn new(center: Point3, normal: Vec3, radius: f64, mat: Arc<dyn Material>) -> Self {
+		let normal = unit_vector(&normal);
+		let radius = radius.max(0.0);
+		let (tangent, bitangent) = orthonormal_basis(&normal);
+
+		// How far the rim reaches along each axis
+		let extent = Vec3::new(
+			radius * (1.0 - normal.x() * normal.x()).max(0.0).sqrt(),
+			radius * (1.0 - normal.y() * normal.y()).max(0.0).sqrt(),
+			radius * (1.0 - normal.z() * normal.z()).max(0.0).sqrt(),
+		);
+		let bbox = Aabb::from_points(center - extent, center + extent);
+		Disk { center, normal, radius, tangent, bitangent, mat, bbox }
+	}
+}
+
+impl Hittable for Disk {
+	fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+		let denom = dot(self.normal, *r.dir());
+
+		// Parallel to the plane
+		if denom.abs() < 1e-8 {
+			return false;
+		}
+
+		let t = dot(self.normal, self.center - *r.origin()) / denom;
+		if !ray_t.contains(t) {
+			return false;
+		}
+
+		let intersection = r.at(t);
+		let offset = intersection - self.center;
+		let dist_squared = offset.length_squared();
+		if dist_squared > self.radius * self.radius {
+			return false;
+		}
+
+		rec.t = t;
+		rec.p = intersection;
+		// Angle around the center and distance from it
+		let angle = dot(offset, self.bitangent).atan2(dot(offset, self.tangent));
+		rec.u = angle / (2.0 * PI) + 0.5;
+		rec.v = if self.radius > 0.0 { dist_squared.sqrt() / self.radius } else { 0.0 };
+		rec.vertex_color = None;
+		rec.mat = self.mat.clone();
+		rec.set_face_normal(r, &self.normal);
+
+		true
+	}
+
+	fn bounding_box(&self) -> Aabb {
+		self.bbox
+	}
+}
@@ -0,0 +1,858 @@
+use std::{collections::{BTreeMap, HashMap}, fmt::Display, fs, io, path::{Path, PathBuf}, sync::Arc};
+
+use serde::Deserialize;
+
+use crate::{camera::Camera, color::Color, hittable::Hittable, hittable_list::HittableList, instance::Instance, mat4::Mat4, material::{Dielectric, Material, PbrMaterial}, mesh::{MeshData, TriangleMesh}, scene::Scene, texture::{ImageTexture, ProductTexture, SolidColor, Texture, VertexColorTexture}, vec3::{unit_vector, Point3, Vec3}};
+
+#[derive(Debug)]
+pub enum GltfError {
+	Io { path: PathBuf, source: io::Error },
+	Parse { path: PathBuf, message: String },
+}
+
+impl Display for GltfError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			GltfError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+			GltfError::Parse { path, message } => write!(f, "{}: {message}", path.display()),
+		}
+	}
+}
+
+impl std::error::Error for GltfError {}
+
+// What a .gltf or .glb file holds that we can render
+pub struct GltfScene {
+	pub world: HittableList,
+	// The first perspective camera in the scene, if there is one
+	pub camera: Option<Camera>,
+}
+
+impl GltfScene {
+	// Without a camera in the file, looks at the whole model from the front
+	pub fn into_scene(self) -> Scene {
+		let camera = self.camera.unwrap_or_else(|| {
+			let bbox = self.world.bounding_box();
+			let mut cam = Camera::new(16.0 / 9.0, 400);
+			cam.fov = 40.0;
+			if bbox.is_finite() && bbox.x.min <= bbox.x.max {
+				let center = bbox.centroid();
+				let radius = 0.5 * Vec3::new(bbox.x.size(), bbox.y.size(), bbox.z.size()).length();
+				let distance = radius / (0.5 * cam.fov).to_radians().sin();
+				cam.look_at = center;
+				cam.look_from = center + distance * unit_vector(&Vec3::new(0.0, 0.3, 1.0));
+			}
+			cam
+		});
+		Scene { world: self.world, camera }
+	}
+}
+
+// Meshes become triangle meshes placed by instances of their nodes, metallic-roughness
+// materials become PbrMaterial and transmissive ones Dielectric
+pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<GltfScene, GltfError> {
+	let path = path.as_ref();
+	let bytes = fs::read(path).map_err(|source| GltfError::Io { path: path.to_path_buf(), source })?;
+	let base_dir = path.parent().unwrap_or(Path::new(""));
+	import(&bytes, base_dir).map_err(|message| GltfError::Parse { path: path.to_path_buf(), message })
+}
+
+// Extensions that change nothing we render, or that we read
+const SUPPORTED_EXTENSIONS: &[&str] = &["KHR_materials_emissive_strength", "KHR_materials_transmission", "KHR_materials_ior"];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+	scene: Option<usize>,
+	#[serde(default)]
+	scenes: Vec<SceneDef>,
+	#[serde(default)]
+	nodes: Vec<NodeDef>,
+	#[serde(default)]
+	meshes: Vec<MeshDef>,
+	#[serde(default)]
+	accessors: Vec<AccessorDef>,
+	#[serde(default)]
+	buffer_views: Vec<BufferViewDef>,
+	#[serde(default)]
+	buffers: Vec<BufferDef>,
+	#[serde(default)]
+	materials: Vec<MaterialDef>,
+	#[serde(default)]
+	textures: Vec<TextureDef>,
+	#[serde(default)]
+	samplers: Vec<SamplerDef>,
+	#[serde(default)]
+	images: Vec<ImageDef>,
+	#[serde(default)]
+	cameras: Vec<CameraDef>,
+	#[serde(default)]
+	extensions_required: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SceneDef {
+	#[serde(default)]
+	nodes: Vec<usize>,
+}
+
+// Either matrix or translation, rotation and scale
+#[derive(Deserialize)]
+struct NodeDef {
+	#[serde(default)]
+	children: Vec<usize>,
+	mesh: Option<usize>,
+	camera: Option<usize>,
+	matrix: Option<[f64; 16]>,
+	translation: Option<[f64; 3]>,
+	// Quaternion as x, y, z, w
+	rotation: Option<[f64; 4]>,
+	scale: Option<[f64; 3]>,
+}
+
+#[derive(Deserialize)]
+struct MeshDef {
+	primitives: Vec<PrimitiveDef>,
+}
+
+#[derive(Deserialize)]
+struct PrimitiveDef {
+	attributes: BTreeMap<String, usize>,
+	indices: Option<usize>,
+	material: Option<usize>,
+	#[serde(default = "triangles_mode")]
+	mode: u32,
+}
+
+fn triangles_mode() -> u32 {
+	4
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessorDef {
+	buffer_view: Option<usize>,
+	#[serde(default)]
+	byte_offset: usize,
+	component_type: u32,
+	#[serde(default)]
+	normalized: bool,
+	count: usize,
+	#[serde(rename = "type")]
+	kind: String,
+	sparse: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferViewDef {
+	buffer: usize,
+	#[serde(default)]
+	byte_offset: usize,
+	byte_length: usize,
+	byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferDef {
+	// None for the binary chunk of a .glb
+	uri: Option<String>,
+	byte_length: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MaterialDef {
+	pbr_metallic_roughness: Option<PbrDef>,
+	emissive_factor: Option<[f64; 3]>,
+	emissive_texture: Option<TextureRef>,
+	#[serde(default)]
+	extensions: MaterialExtensions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PbrDef {
+	base_color_factor: Option<[f64; 4]>,
+	base_color_texture: Option<TextureRef>,
+	metallic_factor: Option<f64>,
+	roughness_factor: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct TextureRef {
+	index: usize,
+}
+
+#[derive(Deserialize, Default)]
+struct MaterialExtensions {
+	#[serde(rename = "KHR_materials_emissive_strength")]
+	emissive_strength: Option<EmissiveStrength>,
+	#[serde(rename = "KHR_materials_transmission")]
+	transmission: Option<Transmission>,
+	#[serde(rename = "KHR_materials_ior")]
+	ior: Option<Ior>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EmissiveStrength {
+	emissive_strength: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Transmission {
+	#[serde(default)]
+	transmission_factor: f64,
+}
+
+#[derive(Deserialize)]
+struct Ior {
+	ior: f64,
+}
+
+#[derive(Deserialize)]
+struct TextureDef {
+	source: Option<usize>,
+	sampler: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SamplerDef {
+	wrap_s: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageDef {
+	uri: Option<String>,
+	buffer_view: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct CameraDef {
+	#[serde(rename = "type")]
+	kind: String,
+	perspective: Option<PerspectiveDef>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PerspectiveDef {
+	// Vertical, in radians
+	yfov: f64,
+	aspect_ratio: Option<f64>,
+}
+
+fn import(bytes: &[u8], base_dir: &Path) -> Result<GltfScene, String> {
+	let (json, bin) = if bytes.starts_with(b"glTF") { split_glb(bytes)? } else { (bytes, None) };
+	let doc: Document = serde_json::from_slice(json).map_err(|e| format!("invalid glTF JSON: {e}"))?;
+	if let Some(ext) = doc.extensions_required.iter().find(|ext| !SUPPORTED_EXTENSIONS.contains(&ext.as_str())) {
+		return Err(format!("requires the unsupported extension {ext}"));
+	}
+
+	let mut buffers = Vec::with_capacity(doc.buffers.len());
+	for (index, buffer) in doc.buffers.iter().enumerate() {
+		let data = match &buffer.uri {
+			Some(uri) => read_uri(uri, base_dir).map_err(|e| format!("buffer {index}: {e}"))?,
+			None => bin.filter(|_| index == 0).ok_or_else(|| format!("buffer {index} has no uri and there is no binary chunk"))?.to_vec(),
+		};
+		if data.len() < buffer.byte_length {
+			return Err(format!("buffer {index} has {} bytes, expected {}", data.len(), buffer.byte_length));
+		}
+		buffers.push(data);
+	}
+
+	let mut importer = Importer { doc: &doc, base_dir, buffers, meshes: HashMap::new(), textures: HashMap::new(), materials: HashMap::new(), world: HittableList::default(), camera: None };
+	let roots = match doc.scenes.get(doc.scene.unwrap_or(0)) {
+		Some(scene) => scene.nodes.clone(),
+		// No scenes, so every node that isn't a child is a root
+		None => (0..doc.nodes.len()).filter(|i| !doc.nodes.iter().any(|n| n.children.contains(i))).collect(),
+	};
+	for root in roots {
+		importer.visit(root, Mat4::IDENTITY, 0)?;
+	}
+	Ok(GltfScene { world: importer.world, camera: importer.camera })
+}
+
+// The JSON chunk and the optional binary chunk of a .glb
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+	let u32_at = |offset: usize| bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+	let version = u32_at(4).ok_or("truncated GLB header")?;
+	if version != 2 {
+		return Err(format!("GLB version {version}, only 2 is supported"));
+	}
+	let length = u32_at(8).ok_or("truncated GLB header")?.min(bytes.len());
+
+	let mut json = None;
+	let mut bin = None;
+	let mut offset = 12;
+	while offset + 8 <= length {
+		let chunk_length = u32_at(offset).ok_or("truncated GLB chunk")?;
+		let chunk_type = u32_at(offset + 4).ok_or("truncated GLB chunk")?;
+		let data = bytes.get(offset + 8..offset + 8 + chunk_length).ok_or("GLB chunk runs past the end of the file")?;
+		match &(chunk_type as u32).to_le_bytes() {
+			b"JSON" => json = json.or(Some(data)),
+			b"BIN\0" => bin = bin.or(Some(data)),
+			_ => {}
+		}
+		offset += 8 + chunk_length;
+	}
+	Ok((json.ok_or("GLB has no JSON chunk")?, bin))
+}
+
+// Data URIs are decoded, anything else is a path relative to the file
+fn read_uri(uri: &str, base_dir: &Path) -> Result<Vec<u8>, String> {
+	if let Some(data) = uri.strip_prefix("data:") {
+		let (header, payload) = data.split_once(',').ok_or("malformed data URI")?;
+		if !header.ends_with(";base64") {
+			return Err("data URIs have to be base64".to_string());
+		}
+		return decode_base64(payload);
+	}
+	let path = base_dir.join(percent_decode(uri));
+	fs::read(&path).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+	let mut out = Vec::with_capacity(text.len() * 3 / 4);
+	let mut acc = 0u32;
+	let mut bits = 0;
+	for c in text.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=') {
+		let value = match c {
+			b'A'..=b'Z' => c - b'A',
+			b'a'..=b'z' => c - b'a' + 26,
+			b'0'..=b'9' => c - b'0' + 52,
+			b'+' | b'-' => 62,
+			b'/' | b'_' => 63,
+			_ => return Err(format!("invalid base64 character `{}`", c as char)),
+		};
+		acc = (acc << 6) | value as u32;
+		bits += 6;
+		if bits >= 8 {
+			bits -= 8;
+			out.push((acc >> bits) as u8);
+		}
+	}
+	Ok(out)
+}
+
+fn percent_decode(uri: &str) -> String {
+	let bytes = uri.as_bytes();
+	let mut out = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+		match (bytes[i], hex) {
+			(b'%', Some(byte)) => {
+				out.push(byte);
+				i += 3;
+			}
+			(byte, _) => {
+				out.push(byte);
+				i += 1;
+			}
+		}
+	}
+	String::from_utf8_lossy(&out).into_owned()
+}
+
+// Vertex data of one primitive in mesh space
+struct Primitive {
+	data: MeshData,
+	triangles: Vec<[u32; 3]>,
+	material: Option<usize>,
+}
+
+struct Importer<'a> {
+	doc: &'a Document,
+	base_dir: &'a Path,
+	buffers: Vec<Vec<u8>>,
+	// Built once and instanced by every node that uses them
+	meshes: HashMap<usize, Option<Arc<dyn Hittable>>>,
+	textures: HashMap<usize, Arc<dyn Texture>>,
+	// By material and whether the primitive has vertex colors
+	materials: HashMap<(Option<usize>, bool), Arc<dyn Material>>,
+	world: HittableList,
+	camera: Option<Camera>,
+}
+
+impl Importer<'_> {
+	fn visit(&mut self, index: usize, parent: Mat4, depth: usize) -> Result<(), String> {
+		// Deeper than any real file, so there must be a cycle
+		if depth > 256 {
+			return Err(format!("node {index} is part of a cycle"));
+		}
+		let node = self.doc.nodes.get(index).ok_or_else(|| format!("node {index} does not exist"))?;
+		let transform = parent * local_transform(node);
+
+		if let Some(mesh) = self.mesh(node.mesh)? {
+			if transform == Mat4::IDENTITY {
+				self.world.add(mesh);
+			} else if let Some(instance) = Instance::try_new(mesh, transform) {
+				// Singular transforms, like a scale of zero, hide the node
+				self.world.add(Arc::new(instance));
+			}
+		}
+		if let (Some(camera), None) = (node.camera, &self.camera) {
+			self.camera = self.camera(camera, &transform)?;
+		}
+		for &child in &node.children {
+			self.visit(child, transform, depth + 1)?;
+		}
+		Ok(())
+	}
+
+	fn camera(&self, index: usize, transform: &Mat4) -> Result<Option<Camera>, String> {
+		let def = self.doc.cameras.get(index).ok_or_else(|| format!("camera {index} does not exist"))?;
+		// Orthographic cameras have no equivalent
+		let (Some(perspective), "perspective") = (&def.perspective, def.kind.as_str()) else {
+			return Ok(None);
+		};
+		let mut cam = Camera::new(perspective.aspect_ratio.unwrap_or(16.0 / 9.0), 400);
+		cam.fov = perspective.yfov.to_degrees();
+		// glTF cameras look down -Z with +Y up
+		cam.look_from = transform.transform_point(&Point3::new(0.0, 0.0, 0.0));
+		cam.look_at = transform.transform_point(&Point3::new(0.0, 0.0, -1.0));
+		cam.vup = unit_vector(&transform.transform_vector(&Vec3::new(0.0, 1.0, 0.0)));
+		cam.defocus_angle = 0.0;
+		Ok(Some(cam))
+	}
+
+	// None for nodes without a mesh and meshes with nothing to render
+	fn mesh(&mut self, mesh: Option<usize>) -> Result<Option<Arc<dyn Hittable>>, String> {
+		let Some(mesh) = mesh else {
+			return Ok(None);
+		};
+		if let Some(built) = self.meshes.get(&mesh) {
+			return Ok(built.clone());
+		}
+		let def = self.doc.meshes.get(mesh).ok_or_else(|| format!("mesh {mesh} does not exist"))?;
+		let mut primitives: Vec<Arc<dyn Hittable>> = Vec::new();
+		for (index, primitive) in def.primitives.iter().enumerate() {
+			if let Some(primitive) = self.primitive(primitive).map_err(|e| format!("mesh {mesh} primitive {index}: {e}"))? {
+				let mat = self.material(primitive.material, !primitive.data.colors.is_empty())?;
+				primitives.push(Arc::new(TriangleMesh::new(Arc::new(primitive.data), primitive.triangles, mat)));
+			}
+		}
+		let built = match primitives.len() {
+			0 => None,
+			1 => primitives.pop(),
+			_ => {
+				let mut list = HittableList::default();
+				for primitive in primitives {
+					list.add(primitive);
+				}
+				Some(Arc::new(list) as Arc<dyn Hittable>)
+			}
+		};
+		self.meshes.insert(mesh, built.clone());
+		Ok(built)
+	}
+
+	// None for points and lines
+	fn primitive(&self, def: &PrimitiveDef) -> Result<Option<Primitive>, String> {
+		if def.mode < 4 {
+			return Ok(None);
+		}
+		let position = *def.attributes.get("POSITION").ok_or("no POSITION attribute")?;
+		let (positions, _) = self.accessor(position, &[3])?;
+		let vertex_count = positions.len() / 3;
+		let mut data = MeshData::new(positions.chunks_exact(3).map(|p| Point3::new(p[0], p[1], p[2])).collect());
+
+		if let Some(&normal) = def.attributes.get("NORMAL") {
+			let (normals, _) = self.accessor(normal, &[3])?;
+			data.normals = normals.chunks_exact(3).map(|n| Vec3::new(n[0], n[1], n[2])).collect();
+		}
+		if let Some(&uv) = def.attributes.get("TEXCOORD_0") {
+			let (uvs, _) = self.accessor(uv, &[2])?;
+			// glTF puts v = 0 at the top of the image
+			data.uvs = uvs.chunks_exact(2).map(|uv| (uv[0], 1.0 - uv[1])).collect();
+		}
+		if let Some(&color) = def.attributes.get("COLOR_0") {
+			// Already linear, alpha is dropped
+			let (colors, components) = self.accessor(color, &[3, 4])?;
+			data.colors = colors.chunks_exact(components).map(|c| Color::new(c[0], c[1], c[2])).collect();
+		}
+		for (name, len) in [("NORMAL", data.normals.len()), ("TEXCOORD_0", data.uvs.len()), ("COLOR_0", data.colors.len())] {
+			if len != 0 && len != vertex_count {
+				return Err(format!("{name} has {len} entries for {vertex_count} positions"));
+			}
+		}
+
+		let indices: Vec<u32> = match def.indices {
+			Some(accessor) => {
+				let (indices, _) = self.accessor(accessor, &[1])?;
+				if let Some(index) = indices.iter().find(|&&i| i as usize >= vertex_count) {
+					return Err(format!("vertex index {index} out of range for {vertex_count} positions"));
+				}
+				indices.iter().map(|&i| i as u32).collect()
+			}
+			None => (0..vertex_count as u32).collect(),
+		};
+		let triangles = match def.mode {
+			4 => indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+			// Every other strip triangle is wound the other way
+			5 => indices.windows(3).enumerate().map(|(i, t)| if i % 2 == 0 { [t[0], t[1], t[2]] } else { [t[1], t[0], t[2]] }).collect(),
+			6 => (1..indices.len().saturating_sub(1)).map(|i| [indices[0], indices[i], indices[i + 1]]).collect(),
+			other => return Err(format!("unknown primitive mode {other}")),
+		};
+		Ok(Some(Primitive { data, triangles, material: def.material }))
+	}
+
+	// Flattened values and how many make up one element, converted to f64
+	fn accessor(&self, index: usize, allowed_components: &[usize]) -> Result<(Vec<f64>, usize), String> {
+		let def = self.doc.accessors.get(index).ok_or_else(|| format!("accessor {index} does not exist"))?;
+		let err = |message: String| format!("accessor {index}: {message}");
+		if def.sparse.is_some() {
+			return Err(err("sparse accessors are not supported".to_string()));
+		}
+		let components = match def.kind.as_str() {
+			"SCALAR" => 1,
+			"VEC2" => 2,
+			"VEC3" => 3,
+			"VEC4" => 4,
+			other => return Err(err(format!("unexpected type {other}"))),
+		};
+		if !allowed_components.contains(&components) {
+			return Err(err(format!("unexpected type {}", def.kind)));
+		}
+		let component_size = match def.component_type {
+			5120 | 5121 => 1,
+			5122 | 5123 => 2,
+			5125 | 5126 => 4,
+			other => return Err(err(format!("unknown component type {other}"))),
+		};
+
+		let Some(view_index) = def.buffer_view else {
+			// No data means all zeros
+			return Ok((vec![0.0; def.count * components], components));
+		};
+		let view = self.doc.buffer_views.get(view_index).ok_or_else(|| err(format!("buffer view {view_index} does not exist")))?;
+		let buffer = self.buffers.get(view.buffer).ok_or_else(|| err(format!("buffer {} does not exist", view.buffer)))?;
+		let view_data = buffer.get(view.byte_offset..view.byte_offset + view.byte_length)
+			.ok_or_else(|| err(format!("buffer view {view_index} runs past the end of buffer {}", view.buffer)))?;
+
+		let element_size = component_size * components;
+		let stride = view.byte_stride.unwrap_or(element_size);
+		if def.count > 0 && def.byte_offset + stride * (def.count - 1) + element_size > view_data.len() {
+			return Err(err(format!("{} elements do not fit in buffer view {view_index}", def.count)));
+		}
+
+		let mut values = Vec::with_capacity(def.count * components);
+		for element in 0..def.count {
+			let start = def.byte_offset + element * stride;
+			for c in view_data[start..start + element_size].chunks_exact(component_size) {
+				let value = match def.component_type {
+					5120 => c[0] as i8 as f64,
+					5121 => c[0] as f64,
+					5122 => i16::from_le_bytes([c[0], c[1]]) as f64,
+					5123 => u16::from_le_bytes([c[0], c[1]]) as f64,
+					5125 => u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f64,
+					_ => f32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f64,
+				};
+				values.push(if def.normalized { normalize(value, def.component_type) } else { value });
+			}
+		}
+		Ok((values, components))
+	}
+
+	fn material(&mut self, index: Option<usize>, vertex_colors: bool) -> Result<Arc<dyn Material>, String> {
+		if let Some(mat) = self.materials.get(&(index, vertex_colors)) {
+			return Ok(mat.clone());
+		}
+		let def = match index {
+			Some(i) => Some(self.doc.materials.get(i).ok_or_else(|| format!("material {i} does not exist"))?),
+			None => None,
+		};
+		let pbr = def.and_then(|d| d.pbr_metallic_roughness.as_ref());
+
+		// Mostly transmissive surfaces render as glass
+		let transmissive = def.and_then(|d| d.extensions.transmission.as_ref()).is_some_and(|t| t.transmission_factor > 0.5);
+		let mat: Arc<dyn Material> = if transmissive {
+			let ior = def.and_then(|d| d.extensions.ior.as_ref()).map_or(1.5, |ior| ior.ior);
+			Arc::new(Dielectric { refraction_index: ior })
+		} else {
+			let [r, g, b, _] = pbr.and_then(|p| p.base_color_factor).unwrap_or([1.0; 4]);
+			let mut base_color: Arc<dyn Texture> = Arc::new(SolidColor::new(Color::new(r, g, b)));
+			if let Some(tex) = pbr.and_then(|p| p.base_color_texture.as_ref()) {
+				base_color = Arc::new(ProductTexture::new(self.texture(tex.index)?, base_color));
+			}
+			if vertex_colors {
+				base_color = Arc::new(ProductTexture::new(base_color, Arc::new(VertexColorTexture::new(Color::new(1.0, 1.0, 1.0)))));
+			}
+
+			let strength = def.and_then(|d| d.extensions.emissive_strength.as_ref()).map_or(1.0, |s| s.emissive_strength);
+			let [er, eg, eb] = def.and_then(|d| d.emissive_factor).unwrap_or([0.0; 3]);
+			let mut emission: Arc<dyn Texture> = Arc::new(SolidColor::new(strength * Color::new(er, eg, eb)));
+			if let Some(tex) = def.and_then(|d| d.emissive_texture.as_ref()) {
+				emission = Arc::new(ProductTexture::new(self.texture(tex.index)?, emission));
+			}
+
+			Arc::new(PbrMaterial {
+				base_color,
+				metallic: pbr.and_then(|p| p.metallic_factor).unwrap_or(1.0).clamp(0.0, 1.0),
+				roughness: pbr.and_then(|p| p.roughness_factor).unwrap_or(1.0).clamp(0.0, 1.0),
+				emission,
+			})
+		};
+		self.materials.insert((index, vertex_colors), mat.clone());
+		Ok(mat)
+	}
+
+	fn texture(&mut self, index: usize) -> Result<Arc<dyn Texture>, String> {
+		if let Some(tex) = self.textures.get(&index) {
+			return Ok(tex.clone());
+		}
+		let def = self.doc.textures.get(index).ok_or_else(|| format!("texture {index} does not exist"))?;
+		let source = def.source.ok_or_else(|| format!("texture {index} has no image"))?;
+		let image = self.doc.images.get(source).ok_or_else(|| format!("image {source} does not exist"))?;
+		let bytes = match (&image.uri, image.buffer_view) {
+			(Some(uri), _) => read_uri(uri, self.base_dir).map_err(|e| format!("image {source}: {e}"))?,
+			(None, Some(view_index)) => {
+				let view = self.doc.buffer_views.get(view_index).ok_or_else(|| format!("buffer view {view_index} does not exist"))?;
+				self.buffers.get(view.buffer).and_then(|b| b.get(view.byte_offset..view.byte_offset + view.byte_length))
+					.ok_or_else(|| format!("image {source} runs past the end of its buffer"))?.to_vec()
+			}
+			(None, None) => return Err(format!("image {source} has neither a uri nor a buffer view")),
+		};
+		let mut tex = ImageTexture::from_bytes(&bytes).map_err(|e| format!("image {source}: {e}"))?;
+		// REPEAT unless the sampler clamps or mirrors
+		tex.repeat = def.sampler.and_then(|s| self.doc.samplers.get(s)).and_then(|s| s.wrap_s).is_none_or(|wrap| wrap == 10497);
+		let tex: Arc<dyn Texture> = Arc::new(tex);
+		self.textures.insert(index, tex.clone());
+		Ok(tex)
+	}
+}
+
+fn local_transform(node: &NodeDef) -> Mat4 {
+	if let Some(matrix) = &node.matrix {
+		return Mat4::from_cols_array(matrix);
+	}
+	let translation = Mat4::translation(node.translation.map_or(Vec3::default(), Vec3::from));
+	let rotation = node.rotation.map_or(Mat4::IDENTITY, |[x, y, z, w]| Mat4::from_quaternion(x, y, z, w));
+	let scale = Mat4::scaling(node.scale.map_or(Vec3::new(1.0, 1.0, 1.0), Vec3::from));
+	translation * rotation * scale
+}
+
+// Normalized integers map onto [0, 1] or [-1, 1]
+fn normalize(value: f64, component_type: u32) -> f64 {
+	match component_type {
+		5120 => (value / 127.0).max(-1.0),
+		5121 => value / 255.0,
+		5122 => (value / 32767.0).max(-1.0),
+		5123 => value / 65535.0,
+		_ => value,
+	}
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{hittable::HitRecord, interval::Interval, ray::Ray};
+
+	fn encode_base64(bytes: &[u8]) -> String {
+		const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+		let mut out = String::new();
+		for chunk in bytes.chunks(3) {
+			let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+			for i in 0..4 {
+				out.push(if i <= chunk.len() { ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char } else { '=' });
+			}
+		}
+		out
+	}
+
+	// A unit quad as a four vertex strip, then four colors interleaved with padding
+	fn buffer() -> Vec<u8> {
+		let mut bytes = Vec::new();
+		for p in [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0]] {
+			bytes.extend(p.iter().flat_map(|c| c.to_le_bytes()));
+		}
+		bytes.extend([0u16, 1, 2, 3].iter().flat_map(|i| i.to_le_bytes()));
+		for color in [[255u8, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255], [255, 255, 255, 255]] {
+			bytes.extend([7u8; 4]);
+			bytes.extend(color);
+		}
+		bytes
+	}
+
+	// The quad sits under a TRS parent and a matrix child, next to a rotated camera
+	fn document(buffer_uri: Option<&str>) -> String {
+		let uri = buffer_uri.map_or(String::new(), |uri| format!(r#""uri": "{uri}", "#));
+		format!(r#"{{
+			"scene": 0,
+			"scenes": [{{"nodes": [0]}}],
+			"nodes": [
+				{{"translation": [0, 0, -5], "scale": [2, 2, 2], "children": [1, 2]}},
+				{{"matrix": [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 10, 0, 0, 1], "mesh": 0}},
+				{{"translation": [0, 1, 0], "rotation": [0, 0.7071067811865476, 0, 0.7071067811865476], "camera": 0}}
+			],
+			"meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0, "COLOR_0": 2}}, "indices": 1, "mode": 5}}]}}],
+			"accessors": [
+				{{"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3"}},
+				{{"bufferView": 1, "componentType": 5123, "count": 4, "type": "SCALAR"}},
+				{{"bufferView": 2, "byteOffset": 4, "componentType": 5121, "normalized": true, "count": 4, "type": "VEC4"}}
+			],
+			"bufferViews": [
+				{{"buffer": 0, "byteLength": 48}},
+				{{"buffer": 0, "byteOffset": 48, "byteLength": 8}},
+				{{"buffer": 0, "byteOffset": 56, "byteLength": 32, "byteStride": 8}}
+			],
+			"buffers": [{{{uri}"byteLength": 88}}],
+			"cameras": [{{"type": "perspective", "perspective": {{"yfov": 0.5, "aspectRatio": 2.0, "znear": 0.1}}}}]
+		}}"#)
+	}
+
+	fn gltf() -> Vec<u8> {
+		document(Some(&format!("data:application/octet-stream;base64,{}", encode_base64(&buffer())))).into_bytes()
+	}
+
+	fn glb() -> Vec<u8> {
+		let mut json = document(None).into_bytes();
+		json.resize(json.len().next_multiple_of(4), b' ');
+		let mut bin = buffer();
+		bin.resize(bin.len().next_multiple_of(4), 0);
+		let mut bytes = b"glTF".to_vec();
+		bytes.extend(2u32.to_le_bytes());
+		bytes.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+		bytes.extend((json.len() as u32).to_le_bytes());
+		bytes.extend(b"JSON");
+		bytes.extend(json);
+		bytes.extend((bin.len() as u32).to_le_bytes());
+		bytes.extend(b"BIN\0");
+		bytes.extend(bin);
+		bytes
+	}
+
+	fn assert_near(a: Vec3, b: Vec3) {
+		assert!((a - b).length() < 1e-9, "{a} != {b}");
+	}
+
+	fn hit(world: &HittableList, origin: Point3) -> Option<HitRecord> {
+		let mut rec = HitRecord::default();
+		let r = Ray::new(origin, Vec3::new(0.0, 0.0, -1.0), 0.0);
+		world.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec).then_some(rec)
+	}
+
+	fn check_scene(scene: &GltfScene) {
+		// Scaled by 2 around the parent and moved by (10, 0, 0) before that
+		let bbox = scene.world.bounding_box();
+		assert!((bbox.x.min - 20.0).abs() < 1e-3 && (bbox.x.max - 22.0).abs() < 1e-3);
+		assert!(bbox.y.min.abs() < 1e-3 && (bbox.y.max - 2.0).abs() < 1e-3);
+		assert!((bbox.z.min + 5.0).abs() < 1e-3 && (bbox.z.max + 5.0).abs() < 1e-3);
+
+		// Both strip triangles face +z, so the second one was rewound
+		for origin in [Point3::new(20.5, 0.5, 0.0), Point3::new(21.5, 1.5, 0.0)] {
+			let rec = hit(&scene.world, origin).expect("the quad is hit");
+			assert!((rec.t - 5.0).abs() < 1e-9);
+			assert!(rec.front_face);
+			assert_near(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+		}
+		assert!(hit(&scene.world, Point3::new(19.5, 0.5, 0.0)).is_none());
+
+		// The camera looks down -Z turned 90 degrees about Y, so along -X
+		let cam = scene.camera.as_ref().expect("the file has a camera");
+		assert_near(cam.look_from, Point3::new(0.0, 2.0, -5.0));
+		assert_near(cam.look_at, Point3::new(-2.0, 2.0, -5.0));
+		assert_near(cam.vup, Vec3::new(0.0, 1.0, 0.0));
+		assert!((cam.fov - 0.5f64.to_degrees()).abs() < 1e-9);
+		assert_eq!(cam.aspect_ratio, 2.0);
+	}
+
+	#[test]
+	fn gltf_with_data_uri() {
+		check_scene(&import(&gltf(), Path::new("")).unwrap_or_else(|e| panic!("{e}")));
+	}
+
+	#[test]
+	fn glb_matches_gltf() {
+		check_scene(&import(&glb(), Path::new("")).unwrap_or_else(|e| panic!("{e}")));
+	}
+
+	#[test]
+	fn split_glb_chunks() {
+		let bytes = glb();
+		let (json, bin) = split_glb(&bytes).unwrap();
+		assert!(json.starts_with(b"{"));
+		assert_eq!(&bin.unwrap()[..88], &buffer()[..]);
+
+		let mut old = bytes.clone();
+		old[4] = 1;
+		assert_eq!(split_glb(&old).err().unwrap(), "GLB version 1, only 2 is supported");
+		assert_eq!(split_glb(&bytes[..6]).err().unwrap(), "truncated GLB header");
+
+		// Claims more JSON than there is
+		let mut long = bytes.clone();
+		long[12..16].copy_from_slice(&10_000u32.to_le_bytes());
+		assert_eq!(split_glb(&long).err().unwrap(), "GLB chunk runs past the end of the file");
+
+		let mut no_json = bytes[..12].to_vec();
+		no_json.extend(&bytes[12 + 8 + u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]) as usize..]);
+		assert_eq!(split_glb(&no_json).err().unwrap(), "GLB has no JSON chunk");
+	}
+
+	#[test]
+	fn base64() {
+		assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+		assert_eq!(decode_base64("aGVs\nbG8").unwrap(), b"hello");
+		// The URL safe alphabet decodes the same
+		assert_eq!(decode_base64("-_8=").unwrap(), decode_base64("+/8=").unwrap());
+		assert_eq!(decode_base64(&encode_base64(&buffer())).unwrap(), buffer());
+		assert_eq!(decode_base64("aGV*").err().unwrap(), "invalid base64 character `*`");
+	}
+
+	#[test]
+	fn percent_decoding() {
+		assert_eq!(percent_decode("my%20model%2Fbody.bin"), "my model/body.bin");
+		assert_eq!(percent_decode("plain.bin"), "plain.bin");
+		// Anything that isn't an escape stays as it is
+		assert_eq!(percent_decode("100%"), "100%");
+		assert_eq!(percent_decode("%zzx"), "%zzx");
+	}
+
+	fn importer<'a>(doc: &'a Document, buffer: Vec<u8>) -> Importer<'a> {
+		Importer { doc, base_dir: Path::new(""), buffers: vec![buffer], meshes: HashMap::new(), textures: HashMap::new(), materials: HashMap::new(), world: HittableList::default(), camera: None }
+	}
+
+	#[test]
+	fn accessor_stride_and_normalized() {
+		let doc: Document = serde_json::from_str(&document(None)).unwrap();
+		let importer = importer(&doc, buffer());
+		let (colors, components) = importer.accessor(2, &[3, 4]).unwrap();
+		assert_eq!(components, 4);
+		assert_eq!(colors, [1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+		let (indices, _) = importer.accessor(1, &[1]).unwrap();
+		assert_eq!(indices, [0.0, 1.0, 2.0, 3.0]);
+		assert_eq!(importer.accessor(0, &[2]).err().unwrap(), "accessor 0: unexpected type VEC3");
+	}
+
+	#[test]
+	fn accessor_bounds() {
+		let doc: Document = serde_json::from_str(r#"{
+			"accessors": [
+				{"bufferView": 0, "byteOffset": 4, "componentType": 5121, "count": 4, "type": "VEC4"},
+				{"bufferView": 0, "byteOffset": 4, "componentType": 5121, "count": 5, "type": "VEC4"},
+				{"bufferView": 1, "componentType": 5120, "normalized": true, "count": 4, "type": "SCALAR"},
+				{"bufferView": 2, "componentType": 5121, "count": 1, "type": "SCALAR"}
+			],
+			"bufferViews": [
+				{"buffer": 0, "byteLength": 32, "byteStride": 8},
+				{"buffer": 0, "byteOffset": 32, "byteLength": 4},
+				{"buffer": 0, "byteOffset": 30, "byteLength": 8}
+			]
+		}"#).unwrap();
+		let mut bytes = vec![0u8; 32];
+		bytes.extend([0x80, 0x81, 0x00, 0x7f]);
+		let importer = importer(&doc, bytes);
+
+		// The last element ends exactly at the end of the view
+		assert_eq!(importer.accessor(0, &[4]).unwrap().0.len(), 16);
+		assert_eq!(importer.accessor(1, &[4]).err().unwrap(), "accessor 1: 5 elements do not fit in buffer view 0");
+		// Both -128 and -127 map to -1
+		assert_eq!(importer.accessor(2, &[1]).unwrap().0, [-1.0, -1.0, 0.0, 1.0]);
+		assert_eq!(importer.accessor(3, &[1]).err().unwrap(), "accessor 3: buffer view 2 runs past the end of buffer 0");
+	}
+}
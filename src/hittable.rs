@@ -1,13 +1,18 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::{interval::Interval, material::{DefaultMaterial, Material}, ray::Ray, vec3::{dot, Point3, Vec3}};
+use crate::{aabb::Aabb, color::Color, interval::Interval, material::{DefaultMaterial, Material}, ray::Ray, vec3::{dot, Point3, Vec3}};
 
 #[derive(Clone)]
 pub struct HitRecord {
     pub p: Point3,
     pub normal: Vec3,
-    pub mat: Rc<dyn Material>,
+    pub mat: Arc<dyn Material>,
     pub t: f64,
+    // Surface coordinates for textures
+    pub u: f64,
+    pub v: f64,
+    // Interpolated from the vertices of meshes that have colors
+    pub vertex_color: Option<Color>,
     pub front_face: bool,
 }
 
@@ -16,8 +21,11 @@ impl Default for HitRecord {
         Self { 
             p: Default::default(), 
             normal: Default::default(), 
-            mat: Rc::new(DefaultMaterial), 
+            mat: Arc::new(DefaultMaterial), 
             t: Default::default(), 
+            u: Default::default(),
+            v: Default::default(),
+            vertex_color: None,
             front_face: Default::default() 
         }
     }
@@ -30,6 +38,68 @@ impl HitRecord {
     }
 }
 
-pub trait Hittable {
+// A stretch of a ray inside an object. A side outside the ray interval it was found
+// for has an infinite t and no surface.
+#[derive(Clone)]
+pub struct Span {
+    pub enter: HitRecord,
+    pub exit: HitRecord,
+}
+
+impl Span {
+    pub fn open_end(t: f64) -> HitRecord {
+        HitRecord { t, ..Default::default() }
+    }
+}
+
+pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
+    fn bounding_box(&self) -> Aabb;
+
+    // Every stretch of ray_t spent inside the object, in order. Found by stepping from
+    // hit to hit, so only meaningful for closed objects.
+    fn spans(&self, r: &Ray, ray_t: Interval) -> Vec<Span> {
+        let mut crossings = Vec::new();
+        let mut t_min = ray_t.min;
+        let mut rec = HitRecord::default();
+        while crossings.len() < MAX_CROSSINGS && self.hit(r, Interval::new(t_min, ray_t.max), &mut rec) {
+            // Past the surface just crossed, without skipping one right behind it
+            t_min = rec.t + 1e-7 * rec.t.abs().max(1.0);
+            crossings.push(rec.clone());
+        }
+
+        // More exits than entries so far means the ray started inside, possibly in
+        // overlapping parts
+        let mut depth = 0;
+        let mut start_depth = 0;
+        for rec in &crossings {
+            depth += if rec.front_face { 1 } else { -1 };
+            start_depth = start_depth.max(-depth);
+        }
+        let mut depth = start_depth;
+        let mut enter = (depth > 0).then(|| Span::open_end(f64::NEG_INFINITY));
+        let mut spans = Vec::new();
+        for rec in crossings {
+            if rec.front_face {
+                depth += 1;
+                if depth == 1 {
+                    enter = Some(rec);
+                }
+            } else {
+                depth -= 1;
+                if depth == 0 {
+                    if let Some(enter) = enter.take() {
+                        spans.push(Span { enter, exit: rec });
+                    }
+                }
+            }
+        }
+        if let Some(enter) = enter {
+            spans.push(Span { enter, exit: Span::open_end(f64::INFINITY) });
+        }
+        spans
+    }
 }
+
+// Keeps broken geometry from stepping along a ray forever
+const MAX_CROSSINGS: usize = 256;
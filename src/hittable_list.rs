@@ -1,13 +1,20 @@
-use crate::{hittable::{HitRecord, Hittable}, interval::Interval};
-use std::rc::Rc;
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval};
+use std::sync::Arc;
 
-#[derive(Default)]
 pub struct HittableList {
-    objects: Vec<Rc<dyn Hittable>>,
+    pub objects: Vec<Arc<dyn Hittable>>,
+    bbox: Aabb,
+}
+
+impl Default for HittableList {
+    fn default() -> Self {
+        Self { objects: Vec::new(), bbox: Aabb::EMPTY }
+    }
 }
 
 impl HittableList {
-    pub fn add(&mut self, object: Rc<dyn Hittable>) {
+    pub fn add(&mut self, object: Arc<dyn Hittable>) {
+        self.bbox = Aabb::from_boxes(&self.bbox, &object.bounding_box());
         self.objects.push(object);
     }
 }
@@ -27,4 +34,8 @@ impl Hittable for HittableList {
         }
         hit_anything
     }
-}
\ No newline at end of file
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
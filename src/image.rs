@@ -0,0 +1,35 @@
+use crate::{color::Color, vec3::Vec3};
+
+// Linear radiance for every pixel, rows top to bottom
+#[derive(Clone, Default)]
+pub struct Image {
+	pub width: usize,
+	pub height: usize,
+	pub pixels: Vec<Color>,
+	pub aovs: Option<Aovs>,
+}
+
+impl Image {
+	pub fn new(width: usize, height: usize) -> Self {
+		Image { width, height, pixels: vec![Color::default(); width * height], aovs: None }
+	}
+	pub fn get(&self, x: usize, y: usize) -> Color {
+		self.pixels[y * self.width + x]
+	}
+	pub fn set(&mut self, x: usize, y: usize, color: Color) {
+		self.pixels[y * self.width + x] = color;
+	}
+}
+
+
+// Arbitrary output variables, extra per-pixel layers from the primary rays
+#[derive(Clone, Default)]
+pub struct Aovs {
+	// Distance from the camera to the first hit, infinite where nothing was hit
+	pub depth: Vec<f64>,
+	// World space normal of the first hit
+	pub normal: Vec<Vec3>,
+	// Attenuation of the first scatter
+	pub albedo: Vec<Color>,
+	pub samples: Vec<u32>,
+}
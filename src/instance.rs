@@ -0,0 +1,164 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, mat4::Mat4, quat::Quat, ray::Ray, vec3::{unit_vector, Vec3}};
+
+// Places a shared object with an affine transform, so one mesh can appear many times
+pub struct Instance {
+	object: Arc<dyn Hittable>,
+	transform: Mat4,
+	inverse: Mat4,
+	// Inverse-transpose, keeps normals perpendicular under non-uniform scaling
+	normal_matrix: Mat4,
+	bbox: Aabb,
+}
+
+impl Instance {
+	// Panics when the transform can't be inverted, like a scale of zero
+	pub fn new(object: Arc<dyn Hittable>, transform: Mat4) -> Self {
+		Self::try_new(object, transform).expect("instance transform has to be invertible")
+	}
+
+	pub fn try_new(object: Arc<dyn Hittable>, transform: Mat4) -> Option<Self> {
+		let inverse = transform.inverse()?;
+		let bbox = transform.transform_box(&object.bounding_box());
+		Some(Instance { object, transform, inverse, normal_matrix: inverse.transpose(), bbox })
+	}
+
+	pub fn transform(&self) -> &Mat4 {
+		&self.transform
+	}
+}
+
+impl Hittable for Instance {
+	fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+		hit_transformed(self.object.as_ref(), r, ray_t, rec, &self.transform, &self.inverse, &self.normal_matrix)
+	}
+
+	fn bounding_box(&self) -> Aabb {
+		self.bbox
+	}
+}
+
+// Where a moving object is at one point in time
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+	pub time: f64,
+	pub translation: Vec3,
+	pub rotation: Quat,
+	pub scale: Vec3,
+}
+
+impl Keyframe {
+	pub fn new(time: f64, translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+		Keyframe { time, translation, rotation, scale }
+	}
+
+	// Scales, then rotates, then translates
+	pub fn matrix(&self) -> Mat4 {
+		Mat4::translation(self.translation) * self.rotation.to_mat4() * Mat4::scaling(self.scale)
+	}
+
+	// Built from the parts, cheaper and more exact than a general inverse
+	fn inverse_matrix(&self) -> Mat4 {
+		let inv_scale = Vec3::new(1.0 / self.scale.x(), 1.0 / self.scale.y(), 1.0 / self.scale.z());
+		Mat4::scaling(inv_scale) * self.rotation.conjugate().to_mat4() * Mat4::translation(-self.translation)
+	}
+
+	// Translation and scale linearly, rotation by slerp
+	fn lerp(a: &Keyframe, b: &Keyframe, t: f64) -> Keyframe {
+		Keyframe {
+			time: (1.0 - t) * a.time + t * b.time,
+			translation: (1.0 - t) * a.translation + t * b.translation,
+			rotation: Quat::slerp(&a.rotation, &b.rotation, t),
+			scale: (1.0 - t) * a.scale + t * b.scale,
+		}
+	}
+}
+
+// An instance whose transform is interpolated between keyframes by the ray time
+pub struct MovingInstance {
+	object: Arc<dyn Hittable>,
+	keyframes: Vec<Keyframe>,
+	bbox: Aabb,
+}
+
+impl MovingInstance {
+	// Keyframes are sorted by time, before the first and after the last the object holds still.
+	// Panics without keyframes or with a zero scale.
+	pub fn new(object: Arc<dyn Hittable>, mut keyframes: Vec<Keyframe>) -> Self {
+		assert!(!keyframes.is_empty(), "moving instance needs at least one keyframe");
+		assert!(keyframes.iter().all(|k| k.scale.x() * k.scale.y() * k.scale.z() != 0.0), "keyframe scale can't be zero");
+		keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+		let bbox = swept_box(&object.bounding_box(), &keyframes);
+		MovingInstance { object, keyframes, bbox }
+	}
+
+	pub fn keyframes(&self) -> &[Keyframe] {
+		&self.keyframes
+	}
+
+	pub fn at(&self, time: f64) -> Keyframe {
+		let next = self.keyframes.partition_point(|k| k.time <= time);
+		if next == 0 {
+			return self.keyframes[0];
+		}
+		if next == self.keyframes.len() {
+			return self.keyframes[next - 1];
+		}
+		let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+		Keyframe::lerp(a, b, (time - a.time) / (b.time - a.time))
+	}
+}
+
+impl Hittable for MovingInstance {
+	fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+		let frame = self.at(r.time());
+		let inverse = frame.inverse_matrix();
+		hit_transformed(self.object.as_ref(), r, ray_t, rec, &frame.matrix(), &inverse, &inverse.transpose())
+	}
+
+	fn bounding_box(&self) -> Aabb {
+		self.bbox
+	}
+}
+
+fn hit_transformed(object: &dyn Hittable, r: &Ray, ray_t: Interval, rec: &mut HitRecord, transform: &Mat4, inverse: &Mat4, normal_matrix: &Mat4) -> bool {
+	// The direction isn't renormalized, so t means the same in both spaces
+	let object_ray = Ray::new(inverse.transform_point(r.origin()), inverse.transform_vector(r.dir()), r.time());
+	if !object.hit(&object_ray, ray_t, rec) {
+		return false;
+	}
+
+	// Facing is kept too, the dot product of direction and normal survives the transform
+	rec.p = transform.transform_point(&rec.p);
+	rec.normal = unit_vector(&normal_matrix.transform_vector(&rec.normal));
+	true
+}
+
+// Most a sample step rotates, smaller steps give tighter boxes
+const MAX_SWEEP_STEP: f64 = PI / 36.0;
+
+// Box over the whole motion. Each segment is sampled finely enough in rotation, and the
+// boxes are padded by how far an arc between two samples can bulge past its chord.
+fn swept_box(object: &Aabb, keyframes: &[Keyframe]) -> Aabb {
+	let mut bbox = keyframes[0].matrix().transform_box(object);
+	// Farthest any point of the object is from its origin
+	let radius = [object.x, object.y, object.z].iter().map(|ax| ax.min.abs().max(ax.max.abs()).powi(2)).sum::<f64>().sqrt();
+
+	for pair in keyframes.windows(2) {
+		let angle = pair[0].rotation.angle_to(&pair[1].rotation);
+		let steps = ((angle / MAX_SWEEP_STEP).ceil() as usize).clamp(1, 64);
+		let mut segment = Aabb::EMPTY;
+		for step in 0..=steps {
+			let frame = Keyframe::lerp(&pair[0], &pair[1], step as f64 / steps as f64);
+			segment = Aabb::from_boxes(&segment, &frame.matrix().transform_box(object));
+		}
+		if angle > 0.0 {
+			let max_scale = pair.iter().flat_map(|k| k.scale.e).fold(0.0_f64, |m, s| m.max(s.abs()));
+			let bulge = radius * max_scale * (1.0 - (0.5 * angle / steps as f64).cos());
+			segment = Aabb::from_intervals(segment.x.expand(2.0 * bulge), segment.y.expand(2.0 * bulge), segment.z.expand(2.0 * bulge));
+		}
+		bbox = Aabb::from_boxes(&bbox, &segment);
+	}
+	bbox
+}
@@ -1,13 +1,19 @@
-use std::f64::{INFINITY, NEG_INFINITY};
-
+#[derive(Clone, Copy)]
 pub struct Interval {
 	pub min: f64,
 	pub max: f64
 }
 impl Interval {
+	pub const EMPTY: Interval = Interval {min: f64::INFINITY, max: f64::NEG_INFINITY};
+	pub const UNIVERSE: Interval = Interval {min: f64::NEG_INFINITY, max: f64::INFINITY};
+
 	pub fn new(min: f64, max: f64) -> Self {
 		Interval {min, max}
 	}
+	// Tightest interval enclosing both
+	pub fn from_intervals(a: &Interval, b: &Interval) -> Self {
+		Interval {min: a.min.min(b.min), max: a.max.max(b.max)}
+	}
 	pub fn size(&self) -> f64 {
 		self.max - self.min
 	}
@@ -27,6 +33,6 @@ impl Interval {
 }
 impl Default for Interval {
 	fn default() -> Self {
-		Interval {min: NEG_INFINITY, max: INFINITY}
+		Interval::UNIVERSE
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,39 @@
+pub mod vec3;
+pub mod color;
+pub mod ray;
+pub mod hittable;
+pub mod sphere;
+pub mod hittable_list;
+pub mod interval;
+pub mod camera;
+pub mod material;
+// Axis aligned bounding box
+pub mod aabb;
+pub mod rng;
+// Bounding volume hierarchy
+pub mod bvh;
+pub mod image;
+pub mod output;
+pub mod tonemap;
+pub mod scene;
+pub mod scenes;
+pub mod texture;
+pub mod perlin;
+pub mod quad;
+pub mod triangle;
+pub mod disk;
+pub mod plane;
+pub mod mesh;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+pub mod mat4;
+pub mod gltf;
+pub mod instance;
+pub mod quat;
+pub mod constant_medium;
+pub mod volume;
+// Constructive solid geometry
+pub mod csg;
+// Signed distance fields
+pub mod sdf;
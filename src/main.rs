@@ -1,72 +1,82 @@
-use std::rc::Rc;
+use std::{env, io::{self, BufWriter}, path::Path, process};
 
-use camera::Camera;
-use color::Color;
-use material::{Dielectric, Lambertian, Metal};
-use rand::{random, thread_rng, Rng};
-use vec3::Vec3;
+use cli::Command;
+use ray_tracing::{bvh::{BvhBuilder, BvhNode}, output::{ppm::write_ppm, write_image}, rng, scene::{load_scene, Scene}, scenes, tonemap::ToneMapping};
 
-use crate::{hittable_list::HittableList, sphere::Sphere, vec3::Point3};
-
-pub mod vec3;
-pub mod color;
-pub mod ray;
-pub mod hittable;
-pub mod sphere;
-mod hittable_list;
-pub mod interval;
-pub mod camera;
-pub mod material;
-// Axis aligned bounding box
-pub mod aabb;
+mod cli;
 
 fn main() {
-    let mut world = HittableList::default();
+    let builtin_names: Vec<&str> = scenes::BUILTIN.iter().map(|(name, _)| *name).collect();
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::usage(&builtin_names));
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\nRun with --help to see the options");
+            process::exit(2);
+        }
+    };
 
-    let ground_mat = Lambertian {albedo: Color::new(0.5,0.5,0.5)};
-    world.add(Rc::new(Sphere::stationary(Point3::new(0.0, -1000.0, 0.0), 1000.0, Rc::new(ground_mat))));
+    rng::seed(options.seed);
+    let scene = if let Some(scene) = scenes::by_name(&options.scene) {
+        scene.unwrap_or_else(|e| {
+            eprintln!("error: {}: {e}", options.scene);
+            process::exit(1);
+        })
+    } else if Path::new(&options.scene).exists() {
+        load_scene(&options.scene).unwrap_or_else(|e| {
+            eprintln!("error: {}: {e}", options.scene);
+            process::exit(1);
+        })
+    } else {
+        eprintln!("error: `{}` is neither a scene file nor a built-in scene ({})", options.scene, builtin_names.join(", "));
+        process::exit(1);
+    };
 
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat: f64 = random();
-            let center = Point3::new(a as f64 + 0.9*random::<f64>(), 0.2, b as f64+ 0.9*random::<f64>());
+    let Scene { world, camera: mut cam } = scene;
+    let world = BvhNode::with_builder(world, &BvhBuilder::new(options.bvh));
+    if !options.quiet {
+        eprintln!("BVH: {}", world.stats());
+    }
 
-            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                if choose_mat < 0.8 {
-                    let albedo = Color::random() * Color::random();
-                    let sphere_material = Lambertian{ albedo };
-                    let center2 = center + Vec3::new(0.0, thread_rng().gen_range(0.0..0.5), 0.0);
-                    world.add(Rc::new(Sphere::moving(center, center2, 0.2, Rc::new(sphere_material))));
-                } else if choose_mat < 0.95 {
-                    let albedo = Color::random() * Color::random();
-                    let fuzz = thread_rng().gen_range(0.0..0.5);
-                    let sphere_material = Metal { albedo, fuzz };
-                    world.add(Rc::new(Sphere::stationary(center, 0.2, Rc::new(sphere_material))));
-                } else {
-                    let sphere_material = Dielectric { refraction_index: 1.5};
-                    world.add(Rc::new(Sphere::stationary(center, 0.2, Rc::new(sphere_material))));
-                }
-            }
+    match (options.width, options.height) {
+        (Some(width), Some(height)) => {
+            cam.image_width = width;
+            cam.fixed_height = Some(height);
+        }
+        (Some(width), None) => cam.image_width = width,
+        (None, Some(height)) => {
+            cam.image_width = ((height as f64 * cam.aspect_ratio).round() as i32).max(1);
+            cam.fixed_height = Some(height);
         }
+        (None, None) => {}
     }
+    if let Some(samples_per_pixel) = options.samples_per_pixel {
+        cam.samples_per_pixel = samples_per_pixel;
+    }
+    if let Some(max_depth) = options.max_depth {
+        cam.max_depth = max_depth;
+    }
+    if let Some((open, close)) = options.shutter {
+        cam.shutter_open = open;
+        cam.shutter_close = close;
+    }
+    cam.threads = options.threads;
+    cam.seed = options.seed;
+    cam.progress = !options.quiet;
+    cam.aovs = options.aovs;
 
-    let material_one = Dielectric { refraction_index: 1.5};
-    world.add(Rc::new(Sphere::stationary(Point3::new(0.0, 1.0, 0.0), 1.0, Rc::new(material_one))));
-    let material_two = Lambertian { albedo: Color::new(0.4, 0.2, 0.1) };
-    world.add(Rc::new(Sphere::stationary(Point3::new(-4.0, 1.0, 0.0), 1.0, Rc::new(material_two))));
-    let material_three = Metal { albedo: Color::new(0.7, 0.6, 0.5), fuzz: 0.0};
-    world.add(Rc::new(Sphere::stationary(Point3::new(4.0, 1.0, 0.0), 1.0, Rc::new(material_three))));
-
-
-    
-    let mut cam = Camera::new(16.0/9.0, 300);
-    cam.samples_per_pixel = 40;
-    cam.max_depth = 10;
-    cam.look_from = Point3::new(13.0, 2.0, 3.0);
-    cam.look_at = Point3::new(0.0, 0.0, 0.0);
-    cam.fov = 20.0;
-    cam.defocus_angle = 0.6;
-    cam.focus_dist = 10.0;
-
-    cam.render(&world);
+    let image = cam.render_to_buffer(&world);
+    let tone = ToneMapping { operator: options.tonemap, exposure: options.exposure, transfer: options.transfer };
+    // Format comes from the extension, P3 PPM to stdout without a path
+    let result = match &options.output {
+        Some(path) => write_image(path, &image, &tone),
+        None => write_ppm(BufWriter::new(io::stdout()), &image, &tone),
+    };
+    if let Err(e) = result {
+        eprintln!("error: failed to write image: {e}");
+        process::exit(1);
+    }
 }
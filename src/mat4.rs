@@ -0,0 +1,199 @@
+use std::ops::Mul;
+
+use crate::{aabb::Aabb, interval::Interval, vec3::{Point3, Vec3}};
+
+// Row-major 4x4 matrix acting on column vectors, so a * b applies b first
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+	pub m: [[f64; 4]; 4],
+}
+
+impl Default for Mat4 {
+	fn default() -> Self {
+		Mat4::IDENTITY
+	}
+}
+
+impl Mat4 {
+	pub const IDENTITY: Mat4 = Mat4 { m: [
+		[1.0, 0.0, 0.0, 0.0],
+		[0.0, 1.0, 0.0, 0.0],
+		[0.0, 0.0, 1.0, 0.0],
+		[0.0, 0.0, 0.0, 1.0],
+	] };
+
+	// Sixteen values column by column, the layout glTF and OpenGL use
+	pub fn from_cols_array(a: &[f64; 16]) -> Self {
+		let mut m = [[0.0; 4]; 4];
+		for (col, values) in a.chunks_exact(4).enumerate() {
+			for (row, value) in values.iter().enumerate() {
+				m[row][col] = *value;
+			}
+		}
+		Mat4 { m }
+	}
+
+	pub fn translation(offset: Vec3) -> Self {
+		let mut m = Mat4::IDENTITY;
+		m.m[0][3] = offset.x();
+		m.m[1][3] = offset.y();
+		m.m[2][3] = offset.z();
+		m
+	}
+
+	pub fn scaling(scale: Vec3) -> Self {
+		let mut m = Mat4::IDENTITY;
+		m.m[0][0] = scale.x();
+		m.m[1][1] = scale.y();
+		m.m[2][2] = scale.z();
+		m
+	}
+
+	// Rotations in degrees, counterclockwise looking down the axis towards the origin
+	pub fn rotation_x(degrees: f64) -> Self {
+		let (sin, cos) = degrees.to_radians().sin_cos();
+		let mut m = Mat4::IDENTITY;
+		m.m[1][1] = cos;
+		m.m[1][2] = -sin;
+		m.m[2][1] = sin;
+		m.m[2][2] = cos;
+		m
+	}
+
+	pub fn rotation_y(degrees: f64) -> Self {
+		let (sin, cos) = degrees.to_radians().sin_cos();
+		let mut m = Mat4::IDENTITY;
+		m.m[0][0] = cos;
+		m.m[0][2] = sin;
+		m.m[2][0] = -sin;
+		m.m[2][2] = cos;
+		m
+	}
+
+	pub fn rotation_z(degrees: f64) -> Self {
+		let (sin, cos) = degrees.to_radians().sin_cos();
+		let mut m = Mat4::IDENTITY;
+		m.m[0][0] = cos;
+		m.m[0][1] = -sin;
+		m.m[1][0] = sin;
+		m.m[1][1] = cos;
+		m
+	}
+
+	// Rotation by the unit quaternion x i + y j + z k + w
+	pub fn from_quaternion(x: f64, y: f64, z: f64, w: f64) -> Self {
+		Mat4 { m: [
+			[1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w), 0.0],
+			[2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w), 0.0],
+			[2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y), 0.0],
+			[0.0, 0.0, 0.0, 1.0],
+		] }
+	}
+
+	pub fn transpose(&self) -> Mat4 {
+		let mut m = [[0.0; 4]; 4];
+		for (row, values) in m.iter_mut().enumerate() {
+			for (col, value) in values.iter_mut().enumerate() {
+				*value = self.m[col][row];
+			}
+		}
+		Mat4 { m }
+	}
+
+	// None when the matrix is singular, like a scale of zero
+	pub fn inverse(&self) -> Option<Mat4> {
+		// Gauss-Jordan elimination with partial pivoting
+		let mut a = self.m;
+		let mut inv = Mat4::IDENTITY.m;
+		for col in 0..4 {
+			let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+			if a[pivot][col].abs() < 1e-12 {
+				return None;
+			}
+			a.swap(col, pivot);
+			inv.swap(col, pivot);
+
+			let scale = 1.0 / a[col][col];
+			for k in 0..4 {
+				a[col][k] *= scale;
+				inv[col][k] *= scale;
+			}
+			for row in 0..4 {
+				if row != col {
+					let factor = a[row][col];
+					for k in 0..4 {
+						a[row][k] -= factor * a[col][k];
+						inv[row][k] -= factor * inv[col][k];
+					}
+				}
+			}
+		}
+		Some(Mat4 { m: inv })
+	}
+
+	// Determinant of the upper 3x3, negative when the transform mirrors
+	pub fn determinant3(&self) -> f64 {
+		let m = &self.m;
+		m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+			- m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+			+ m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+	}
+
+	pub fn transform_point(&self, p: &Point3) -> Point3 {
+		let m = &self.m;
+		Point3::new(
+			m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3],
+			m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3],
+			m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3],
+		)
+	}
+
+	// Ignores the translation
+	pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+		let m = &self.m;
+		Vec3::new(
+			m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+			m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+			m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+		)
+	}
+
+	// Smallest box around the transformed box, from the extremes of every matrix entry
+	// times its input axis (Arvo), which also keeps infinite boxes infinite
+	pub fn transform_box(&self, b: &Aabb) -> Aabb {
+		let axes = [b.x, b.y, b.z];
+		if axes.iter().any(|ax| ax.min > ax.max) {
+			return Aabb::EMPTY;
+		}
+		let mut out = [Interval::new(0.0, 0.0); 3];
+		for (row, ax) in out.iter_mut().enumerate() {
+			ax.min = self.m[row][3];
+			ax.max = self.m[row][3];
+			for (col, input) in axes.iter().enumerate() {
+				let factor = self.m[row][col];
+				// Skipped so zero times infinity can't make a NaN
+				if factor == 0.0 {
+					continue;
+				}
+				let (a, b) = (factor * input.min, factor * input.max);
+				ax.min += a.min(b);
+				ax.max += a.max(b);
+			}
+		}
+		Aabb::from_intervals(out[0], out[1], out[2])
+	}
+}
+
+impl Mul<Mat4> for Mat4 {
+	type Output = Mat4;
+
+	fn mul(self, rhs: Mat4) -> Self::Output {
+		let mut m = [[0.0; 4]; 4];
+		for (row, values) in m.iter_mut().enumerate() {
+			for (col, value) in values.iter_mut().enumerate() {
+				*value = (0..4).map(|k| self.m[row][k] * rhs.m[k][col]).sum();
+			}
+		}
+		Mat4 { m }
+	}
+}
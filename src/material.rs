@@ -1,9 +1,13 @@
-use rand::random;
+use std::{f64::consts::PI, sync::Arc};
 
-use crate::{color::Color, hittable::HitRecord, ray::Ray, vec3::{dot, random_unit_vector, reflect, refract, unit_vector}};
+use crate::{color::Color, hittable::HitRecord, ray::Ray, rng::random_double, texture::{SolidColor, Texture}, vec3::{dot, orthonormal_basis, random_unit_vector, reflect, refract, unit_vector}};
 
-pub trait Material {
+pub trait Material: Send + Sync {
 	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool;
+	// Light given off at the hit point, nothing unless the material is a light
+	fn emitted(&self, _rec: &HitRecord) -> Color {
+		Color::default()
+	}
 }
 pub struct DefaultMaterial;
 impl Material for DefaultMaterial {
@@ -12,7 +16,15 @@ impl Material for DefaultMaterial {
 	}
 }
 
-pub struct Lambertian { pub albedo: Color }
+pub struct Lambertian { pub tex: Arc<dyn Texture> }
+impl Lambertian {
+	pub fn new(albedo: Color) -> Self {
+		Lambertian { tex: Arc::new(SolidColor::new(albedo)) }
+	}
+	pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+		Lambertian { tex }
+	}
+}
 impl Material for Lambertian {
 	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
 		let mut scatter_dir = rec.normal + random_unit_vector();
@@ -23,18 +35,85 @@ impl Material for Lambertian {
 		}
 
 		*scattered = Ray::new(rec.p, scatter_dir, r_in.time());
-		*attenuation = self.albedo;
+		*attenuation = self.tex.value_at(rec);
+		true
+	}
+}
+
+// Scatters the same in every direction, the phase function of fog and smoke
+pub struct Isotropic { pub tex: Arc<dyn Texture> }
+impl Isotropic {
+	pub fn new(albedo: Color) -> Self {
+		Isotropic { tex: Arc::new(SolidColor::new(albedo)) }
+	}
+	pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+		Isotropic { tex }
+	}
+}
+impl Material for Isotropic {
+	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		*scattered = Ray::new(rec.p, random_unit_vector(), r_in.time());
+		*attenuation = self.tex.value_at(rec);
+		true
+	}
+}
+
+// Phase function of volumes that scatter forward (g > 0) or back (g < 0), like clouds.
+// Particles that don't scatter absorb, giving off the emission.
+pub struct HenyeyGreenstein { pub albedo: Arc<dyn Texture>, pub g: f64, pub emission: Arc<dyn Texture> }
+impl HenyeyGreenstein {
+	pub fn new(albedo: Color, g: f64) -> Self {
+		Self::from_textures(Arc::new(SolidColor::new(albedo)), g, Arc::new(SolidColor::new(Color::default())))
+	}
+	pub fn from_textures(albedo: Arc<dyn Texture>, g: f64, emission: Arc<dyn Texture>) -> Self {
+		// Exactly 1 or -1 would be a delta
+		HenyeyGreenstein { albedo, g: g.clamp(-0.999, 0.999), emission }
+	}
+	// Cosine of the angle between the old and the new direction
+	fn sample_cos_theta(&self) -> f64 {
+		let xi = random_double();
+		if self.g.abs() < 1e-3 {
+			return 1.0 - 2.0 * xi;
+		}
+		let g = self.g;
+		let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+		((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+	}
+}
+impl Material for HenyeyGreenstein {
+	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		let forward = unit_vector(r_in.dir());
+		let (t, b) = orthonormal_basis(&forward);
+		let cos_theta = self.sample_cos_theta();
+		let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+		let phi = 2.0 * PI * random_double();
+		let direction = sin_theta * phi.cos() * t + sin_theta * phi.sin() * b + cos_theta * forward;
+
+		*scattered = Ray::new(rec.p, direction, r_in.time());
+		*attenuation = self.albedo.value_at(rec);
 		true
 	}
+	fn emitted(&self, rec: &HitRecord) -> Color {
+		let absorbed = Color::new(1.0, 1.0, 1.0) - self.albedo.value_at(rec);
+		absorbed * self.emission.value_at(rec)
+	}
 }
 
-pub struct Metal { pub albedo: Color, pub fuzz: f64 }
+pub struct Metal { pub tex: Arc<dyn Texture>, pub fuzz: f64 }
+impl Metal {
+	pub fn new(albedo: Color, fuzz: f64) -> Self {
+		Metal { tex: Arc::new(SolidColor::new(albedo)), fuzz }
+	}
+	pub fn from_texture(tex: Arc<dyn Texture>, fuzz: f64) -> Self {
+		Metal { tex, fuzz }
+	}
+}
 impl Material for Metal {
 	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
 		let reflected = reflect(r_in.dir(), &rec.normal);
 		let reflected = unit_vector(&reflected) + (self.fuzz * random_unit_vector());
 		*scattered = Ray::new(rec.p, reflected, r_in.time());
-		*attenuation = self.albedo;
+		*attenuation = self.tex.value_at(rec);
 		true
 	}
 }
@@ -56,7 +135,7 @@ impl Material for Dielectric {
 		let cos_theta = dot(-unit_dir, rec.normal).min(1.0);
 		let sin_theta = (1.0 - cos_theta*cos_theta).sqrt();
 
-		let direction = if ri * sin_theta > 1.0 || Self::reflectance(cos_theta, ri) > random() {
+		let direction = if ri * sin_theta > 1.0 || Self::reflectance(cos_theta, ri) > random_double() {
 			reflect(&unit_dir, &rec.normal)
 		} else {
 			refract(&unit_dir, &rec.normal, ri)
@@ -65,4 +144,92 @@ impl Material for Dielectric {
 		*scattered = Ray::new(rec.p, direction, r_in.time());
 		true
 	}
-}
\ No newline at end of file
+}
+
+// Emits light from its texture and absorbs everything that hits it
+pub struct DiffuseLight { pub tex: Arc<dyn Texture> }
+impl DiffuseLight {
+	pub fn new(emit: Color) -> Self {
+		DiffuseLight { tex: Arc::new(SolidColor::new(emit)) }
+	}
+	pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+		DiffuseLight { tex }
+	}
+}
+impl Material for DiffuseLight {
+	fn scatter(&self, _: &Ray, _: &HitRecord, _: &mut Color, _: &mut Ray) -> bool {
+		false
+	}
+	fn emitted(&self, rec: &HitRecord) -> Color {
+		self.tex.value_at(rec)
+	}
+}
+
+// Metallic-roughness model as in glTF. Metals reflect tinted by the base color,
+// other surfaces pick between a white Fresnel reflection and diffuse.
+pub struct PbrMaterial {
+	pub base_color: Arc<dyn Texture>,
+	pub metallic: f64,
+	// Perceptual roughness, squared to get the fuzz
+	pub roughness: f64,
+	pub emission: Arc<dyn Texture>,
+}
+impl PbrMaterial {
+	pub fn new(base_color: Color, metallic: f64, roughness: f64) -> Self {
+		PbrMaterial {
+			base_color: Arc::new(SolidColor::new(base_color)),
+			metallic,
+			roughness,
+			emission: Arc::new(SolidColor::new(Color::default())),
+		}
+	}
+}
+impl Material for PbrMaterial {
+	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		let unit_dir = unit_vector(r_in.dir());
+		let fuzz = self.roughness * self.roughness;
+		let glossy = || reflect(&unit_dir, &rec.normal) + fuzz * random_unit_vector();
+
+		if random_double() < self.metallic {
+			*scattered = Ray::new(rec.p, glossy(), r_in.time());
+			*attenuation = self.base_color.value_at(rec);
+			return dot(*scattered.dir(), rec.normal) > 0.0;
+		}
+
+		// Schlick's approximation with the 4% reflectance of common dielectrics
+		let cosine = dot(-unit_dir, rec.normal).clamp(0.0, 1.0);
+		let fresnel = 0.04 + 0.96 * (1.0 - cosine).powi(5);
+		if random_double() < fresnel {
+			*scattered = Ray::new(rec.p, glossy(), r_in.time());
+			*attenuation = Color::new(1.0, 1.0, 1.0);
+			return dot(*scattered.dir(), rec.normal) > 0.0;
+		}
+
+		let mut scatter_dir = rec.normal + random_unit_vector();
+		if scatter_dir.near_zero() {
+			scatter_dir = rec.normal
+		}
+		*scattered = Ray::new(rec.p, scatter_dir, r_in.time());
+		*attenuation = self.base_color.value_at(rec);
+		true
+	}
+	fn emitted(&self, rec: &HitRecord) -> Color {
+		self.emission.value_at(rec)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::texture::VertexColorTexture;
+
+	// Lights see the whole hit, like scatter does, so vertex colors reach emission too
+	#[test]
+	fn emission_from_vertex_colors() {
+		let light = DiffuseLight::from_texture(Arc::new(VertexColorTexture::new(Color::new(1.0, 1.0, 1.0))));
+		let rec = HitRecord { vertex_color: Some(Color::new(0.2, 0.4, 0.8)), ..Default::default() };
+		assert!((light.emitted(&rec) - Color::new(0.2, 0.4, 0.8)).near_zero());
+		let rec = HitRecord::default();
+		assert!((light.emitted(&rec) - Color::new(1.0, 1.0, 1.0)).near_zero());
+	}
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use crate::{aabb::Aabb, bvh::{Bvh, BvhBuilder}, color::Color, hittable::{HitRecord, Hittable}, interval::Interval, material::{Lambertian, Material}, ray::Ray, texture::VertexColorTexture, vec3::{cross, dot, unit_vector, Point3, Vec3}};
+
+// Vertex attributes that any number of meshes can index into.
+// Normals, UVs and colors are either empty or have one entry per position.
+#[derive(Default)]
+pub struct MeshData {
+	pub positions: Vec<Point3>,
+	pub normals: Vec<Vec3>,
+	pub uvs: Vec<(f64, f64)>,
+	// Linear, for VertexColorTexture
+	pub colors: Vec<Color>,
+}
+
+impl MeshData {
+	pub fn new(positions: Vec<Point3>) -> Self {
+		MeshData { positions, ..Default::default() }
+	}
+}
+
+// A single mesh read from a file, before it has a material
+pub struct LoadedMesh {
+	pub data: MeshData,
+	pub triangles: Vec<[u32; 3]>,
+}
+
+impl LoadedMesh {
+	// Diffuse over the vertex colors when there are any, plain grey otherwise
+	pub fn default_material(&self) -> Arc<dyn Material> {
+		if self.data.colors.is_empty() {
+			Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)))
+		} else {
+			Arc::new(Lambertian::from_texture(Arc::new(VertexColorTexture::new(Color::new(0.8, 0.8, 0.8)))))
+		}
+	}
+
+	pub fn into_mesh(self, mat: Arc<dyn Material>) -> TriangleMesh {
+		TriangleMesh::new(Arc::new(self.data), self.triangles, mat)
+	}
+}
+
+// Indexed triangles over shared vertex data, with a BVH of its own
+pub struct TriangleMesh {
+	data: Arc<MeshData>,
+	triangles: Vec<[u32; 3]>,
+	mat: Arc<dyn Material>,
+	bvh: Bvh,
+}
+
+impl TriangleMesh {
+	// Panics if a triangle refers to a vertex that isn't in data
+	pub fn new(data: Arc<MeshData>, triangles: Vec<[u32; 3]>, mat: Arc<dyn Material>) -> Self {
+		Self::with_builder(data, triangles, mat, &BvhBuilder::default())
+	}
+
+	pub fn with_builder(data: Arc<MeshData>, triangles: Vec<[u32; 3]>, mat: Arc<dyn Material>, builder: &BvhBuilder) -> Self {
+		let vertex_count = data.positions.len();
+		assert!(data.normals.is_empty() || data.normals.len() == vertex_count, "mesh needs one normal per position");
+		assert!(data.uvs.is_empty() || data.uvs.len() == vertex_count, "mesh needs one uv per position");
+		assert!(data.colors.is_empty() || data.colors.len() == vertex_count, "mesh needs one color per position");
+		assert!(triangles.iter().flatten().all(|&i| (i as usize) < vertex_count), "mesh triangle refers to a missing vertex");
+
+		let boxes: Vec<Aabb> = triangles.iter().map(|tri| {
+			let [p0, p1, p2] = tri.map(|i| data.positions[i as usize]);
+			Aabb::from_boxes(&Aabb::from_points(p0, p1), &Aabb::from_points(p0, p2))
+		}).collect();
+		let bvh = builder.build(&boxes);
+		TriangleMesh { data, triangles, mat, bvh }
+	}
+
+	pub fn data(&self) -> &Arc<MeshData> {
+		&self.data
+	}
+
+	pub fn triangle_count(&self) -> usize {
+		self.triangles.len()
+	}
+
+	// Möller–Trumbore, returns t and the barycentric weights of the second and third vertex
+	fn intersect(&self, index: usize, r: &Ray, ray_t: Interval) -> Option<(f64, f64, f64)> {
+		let [p0, p1, p2] = self.triangles[index].map(|i| self.data.positions[i as usize]);
+		let edge1 = p1 - p0;
+		let edge2 = p2 - p0;
+
+		let pvec = cross(*r.dir(), edge2);
+		let det = dot(edge1, pvec);
+		// Parallel to the triangle
+		if det.abs() < 1e-12 {
+			return None;
+		}
+		let inv_det = 1.0 / det;
+
+		let tvec = *r.origin() - p0;
+		let b1 = dot(tvec, pvec) * inv_det;
+		if !(0.0..=1.0).contains(&b1) {
+			return None;
+		}
+
+		let qvec = cross(tvec, edge1);
+		let b2 = dot(*r.dir(), qvec) * inv_det;
+		if b2 < 0.0 || b1 + b2 > 1.0 {
+			return None;
+		}
+
+		let t = dot(edge2, qvec) * inv_det;
+		ray_t.surrounds(t).then_some((t, b1, b2))
+	}
+}
+
+impl Hittable for TriangleMesh {
+	fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+		let mut closest = None;
+		self.bvh.hit(r, ray_t, |i, t| {
+			let (t, b1, b2) = self.intersect(i, r, t)?;
+			closest = Some((i, t, b1, b2));
+			Some(t)
+		});
+		let Some((index, t, b1, b2)) = closest else {
+			return false;
+		};
+
+		let tri = self.triangles[index].map(|i| i as usize);
+		let b0 = 1.0 - b1 - b2;
+		let [p0, p1, p2] = tri.map(|i| self.data.positions[i]);
+		rec.t = t;
+		rec.p = r.at(t);
+		let geometric_normal = unit_vector(&cross(p1 - p0, p2 - p0));
+		let shading_normal = if self.data.normals.is_empty() {
+			None
+		} else {
+			let [n0, n1, n2] = tri.map(|i| self.data.normals[i]);
+			Some(b0 * n0 + b1 * n1 + b2 * n2).filter(|n| !n.near_zero()).map(|n| unit_vector(&n))
+		};
+		match shading_normal {
+			// Vertex normals decide which side is the outside, whatever the winding
+			Some(n) => {
+				let outward = if dot(geometric_normal, n) < 0.0 { -geometric_normal } else { geometric_normal };
+				rec.set_face_normal(r, &outward);
+				rec.normal = if rec.front_face { n } else { -n };
+			}
+			None => rec.set_face_normal(r, &geometric_normal),
+		}
+		(rec.u, rec.v) = if self.data.uvs.is_empty() {
+			(b1, b2)
+		} else {
+			let [uv0, uv1, uv2] = tri.map(|i| self.data.uvs[i]);
+			(b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0, b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1)
+		};
+		rec.vertex_color = if self.data.colors.is_empty() {
+			None
+		} else {
+			let [c0, c1, c2] = tri.map(|i| self.data.colors[i]);
+			Some(b0 * c0 + b1 * c1 + b2 * c2)
+		};
+		rec.mat = self.mat.clone();
+
+		true
+	}
+
+	fn bounding_box(&self) -> Aabb {
+		self.bvh.bounding_box()
+	}
+}
@@ -0,0 +1,563 @@
+use std::{collections::{BTreeMap, HashMap}, fmt::Display, fs, io, path::{Path, PathBuf}, sync::Arc};
+
+use crate::{color::{srgb_to_linear, Color}, hittable_list::HittableList, material::{Dielectric, DiffuseLight, Lambertian, Material, Metal}, mesh::{MeshData, TriangleMesh}, texture::{ImageTexture, ProductTexture, SolidColor, Texture, VertexColorTexture}, vec3::{cross, unit_vector, Point3, Vec3}};
+
+#[derive(Debug)]
+pub enum ObjError {
+	Io { path: PathBuf, source: io::Error },
+	// Line is 1-based and in the file named by path, which may be an .mtl
+	Parse { path: PathBuf, line: usize, message: String },
+}
+
+impl Display for ObjError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+			ObjError::Parse { path, line, message } => write!(f, "{}: line {line}: {message}", path.display()),
+		}
+	}
+}
+
+impl std::error::Error for ObjError {}
+
+// Triangles of one material, indexing into the model's shared vertices
+pub struct ObjGroup {
+	// None for faces before any `usemtl`
+	pub material_name: Option<String>,
+	pub material: Arc<dyn Material>,
+	pub triangles: Vec<[u32; 3]>,
+}
+
+pub struct ObjModel {
+	pub data: Arc<MeshData>,
+	// In the order the materials are first used
+	pub groups: Vec<ObjGroup>,
+}
+
+impl ObjModel {
+	// One mesh per material, all sharing the vertex buffers
+	pub fn into_hittables(self) -> HittableList {
+		let mut list = HittableList::default();
+		for group in self.groups {
+			list.add(Arc::new(TriangleMesh::new(self.data.clone(), group.triangles, group.material)));
+		}
+		list
+	}
+}
+
+// Reads an .obj and every .mtl it refers to, relative to the .obj
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<ObjModel, ObjError> {
+	let path = path.as_ref();
+	let src = fs::read_to_string(path).map_err(|source| ObjError::Io { path: path.to_path_buf(), source })?;
+	ObjParser { path, positions: Vec::new(), colors: Vec::new(), normals: Vec::new(), uvs: Vec::new(), materials: BTreeMap::new() }.parse(&src)
+}
+
+// Materials by name, mapped onto the crate's materials:
+// Ke to DiffuseLight, transparency to Dielectric, metallic or mostly specular to Metal
+// and everything else to Lambertian
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> Result<BTreeMap<String, Arc<dyn Material>>, ObjError> {
+	let path = path.as_ref();
+	let src = fs::read_to_string(path).map_err(|source| ObjError::Io { path: path.to_path_buf(), source })?;
+	let base_dir = path.parent().unwrap_or(Path::new(""));
+	let line_error = |line: usize, message: String| ObjError::Parse { path: path.to_path_buf(), line, message };
+
+	let mut materials = BTreeMap::new();
+	let mut current: Option<(String, MtlDef)> = None;
+	for (index, line) in src.lines().enumerate() {
+		let line_no = index + 1;
+		let mut tokens = Tokens::new(line, line_no, path);
+		let Some(keyword) = tokens.next_word() else {
+			continue;
+		};
+		if keyword == "newmtl" {
+			let name = tokens.rest().ok_or_else(|| line_error(line_no, "`newmtl` without a name".to_string()))?;
+			if let Some((name, def)) = current.take() {
+				materials.insert(name, def.build());
+			}
+			current = Some((name.to_string(), MtlDef::default()));
+			continue;
+		}
+		let Some((_, def)) = current.as_mut() else {
+			return Err(line_error(line_no, format!("`{keyword}` before any `newmtl`")));
+		};
+		match keyword {
+			"Kd" => def.kd = Some(tokens.color()?),
+			"Ks" => def.ks = Some(tokens.color()?),
+			"Ke" => def.ke = Some(tokens.color()?),
+			"Ns" => def.ns = Some(tokens.number()?),
+			"Ni" => def.ni = Some(tokens.number()?),
+			"d" => def.dissolve = Some(tokens.number()?),
+			"Tr" => def.dissolve = Some(1.0 - tokens.number::<f64>()?),
+			"Pm" => def.metallic = Some(tokens.number()?),
+			"Pr" => def.roughness = Some(tokens.number()?),
+			"illum" => def.illum = Some(tokens.number()?),
+			"map_Kd" => {
+				let name = tokens.rest().and_then(map_file_name)
+					.ok_or_else(|| line_error(line_no, "`map_Kd` without a file name".to_string()))?;
+				let tex_path = base_dir.join(name);
+				let tex = ImageTexture::load(&tex_path).map_err(|e| line_error(line_no, format!("cannot load {}: {e}", tex_path.display())))?;
+				def.map_kd = Some(Arc::new(tex));
+			}
+			// Other maps and exporter extensions don't change how we render
+			_ => {}
+		}
+	}
+	if let Some((name, def)) = current {
+		materials.insert(name, def.build());
+	}
+	Ok(materials)
+}
+
+// Skips options like `-s 1 1 1` or `-clamp on`, everything after them is the file
+// name, spaces included
+fn map_file_name(rest: &str) -> Option<&str> {
+	let mut rest = rest.trim_start();
+	loop {
+		let (word, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+		// How many arguments follow, at least and at most
+		let (required, max) = match word {
+			"-blendu" | "-blendv" | "-boost" | "-texres" | "-clamp" | "-bm" | "-imfchan" | "-type" => (1, 1),
+			"-mm" => (1, 2),
+			"-o" | "-s" | "-t" => (1, 3),
+			_ => break,
+		};
+		rest = after.trim_start();
+		for i in 0..max {
+			let (arg, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+			// Optional arguments are always numbers
+			if i >= required && arg.parse::<f64>().is_err() {
+				break;
+			}
+			rest = after.trim_start();
+		}
+	}
+	Some(rest.trim_end()).filter(|name| !name.is_empty())
+}
+
+// What one `newmtl` block sets
+#[derive(Default)]
+struct MtlDef {
+	kd: Option<Color>,
+	ks: Option<Color>,
+	ke: Option<Color>,
+	ns: Option<f64>,
+	ni: Option<f64>,
+	dissolve: Option<f64>,
+	metallic: Option<f64>,
+	roughness: Option<f64>,
+	illum: Option<u32>,
+	map_kd: Option<Arc<dyn Texture>>,
+}
+
+impl MtlDef {
+	fn build(self) -> Arc<dyn Material> {
+		let is_black = |c: Option<Color>| c.is_none_or(|c| c.x() <= 0.0 && c.y() <= 0.0 && c.z() <= 0.0);
+		let has_map = self.map_kd.is_some();
+		// Kd tints the map when both are given
+		let diffuse: Arc<dyn Texture> = match (self.map_kd, self.kd) {
+			(Some(tex), Some(kd)) => Arc::new(ProductTexture::new(tex, Arc::new(SolidColor::new(kd)))),
+			(Some(tex), None) => tex,
+			(None, kd) => Arc::new(SolidColor::new(kd.unwrap_or(Color::new(0.8, 0.8, 0.8)))),
+		};
+
+		if !is_black(self.ke) {
+			return Arc::new(DiffuseLight::new(self.ke.unwrap_or_default()));
+		}
+		// Illumination models 4, 6, 7 and 9 are the glass ones
+		if self.dissolve.is_some_and(|d| d < 1.0) || matches!(self.illum, Some(4 | 6 | 7 | 9)) {
+			return Arc::new(Dielectric { refraction_index: self.ni.unwrap_or(1.5) });
+		}
+		// Phong exponent to a rough fuzz, 0 for a mirror
+		let fuzz = self.roughness.unwrap_or_else(|| self.ns.map_or(0.0, |ns| (2.0 / (ns.max(0.0) + 2.0)).sqrt())).clamp(0.0, 1.0);
+		if self.metallic.is_some_and(|m| m >= 0.5) {
+			return Arc::new(Metal::from_texture(diffuse, fuzz));
+		}
+		if self.illum == Some(3) || (!is_black(self.ks) && is_black(self.kd) && !has_map) {
+			return Arc::new(Metal::new(self.ks.unwrap_or(Color::new(1.0, 1.0, 1.0)), fuzz));
+		}
+		Arc::new(Lambertian::from_texture(diffuse))
+	}
+}
+
+struct ObjParser<'a> {
+	path: &'a Path,
+	positions: Vec<Point3>,
+	// From the `v x y z r g b` extension, linear
+	colors: Vec<Option<Color>>,
+	normals: Vec<Vec3>,
+	uvs: Vec<(f64, f64)>,
+	materials: BTreeMap<String, Arc<dyn Material>>,
+}
+
+// Indices of one face corner into the position, uv and normal lists
+type Corner = (usize, Option<usize>, Option<usize>);
+
+impl ObjParser<'_> {
+	fn parse(mut self, src: &str) -> Result<ObjModel, ObjError> {
+		let base_dir = self.path.parent().unwrap_or(Path::new(""));
+		let mut data = MeshData::default();
+		// Faces share a vertex only when position, uv and normal all match
+		let mut vertices: HashMap<Corner, u32> = HashMap::new();
+		let mut groups: Vec<ObjGroup> = Vec::new();
+		let mut current_group = None;
+		let mut any_normals = false;
+		let mut any_uvs = false;
+		// Corners without a normal get their face's, filled in at the end
+		let mut missing_normals: Vec<(u32, Vec3)> = Vec::new();
+
+		for (index, line) in src.lines().enumerate() {
+			let line_no = index + 1;
+			let mut tokens = Tokens::new(line, line_no, self.path);
+			let Some(keyword) = tokens.next_word() else {
+				continue;
+			};
+			match keyword {
+				"v" => {
+					self.positions.push(tokens.vec3()?);
+					let extra: [Option<f64>; 3] = [tokens.optional_number()?, tokens.optional_number()?, tokens.optional_number()?];
+					// A fourth value on its own is a weight, not a color
+					let color = match extra {
+						[Some(r), Some(g), Some(b)] => Some(Color::new(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b))),
+						_ => None,
+					};
+					self.colors.push(color);
+				}
+				"vn" => self.normals.push(tokens.vec3()?),
+				"vt" => {
+					let u = tokens.number()?;
+					// v is optional for 1D textures
+					let v = tokens.optional_number()?.unwrap_or(0.0);
+					self.uvs.push((u, v));
+				}
+				"f" => {
+					let corners = tokens.map(|word| self.corner(word, line_no)).collect::<Result<Vec<_>, _>>()?;
+					if corners.len() < 3 {
+						return Err(self.error(line_no, format!("face with {} vertices, needs at least 3", corners.len())));
+					}
+					let group = *current_group.get_or_insert_with(|| {
+						groups.push(ObjGroup { material_name: None, material: default_material(), triangles: Vec::new() });
+						groups.len() - 1
+					});
+
+					let indices: Vec<u32> = corners.iter().map(|&corner| {
+						*vertices.entry(corner).or_insert_with(|| {
+							let (p, t, n) = corner;
+							data.positions.push(self.positions[p]);
+							data.colors.push(self.colors[p].unwrap_or(Color::new(0.8, 0.8, 0.8)));
+							data.uvs.push(t.map_or((0.0, 0.0), |t| self.uvs[t]));
+							data.normals.push(n.map_or(Vec3::default(), |n| self.normals[n]));
+							any_uvs |= t.is_some();
+							any_normals |= n.is_some();
+							(data.positions.len() - 1) as u32
+						})
+					}).collect();
+
+					// Fan out n-gons from the first corner
+					for i in 1..indices.len() - 1 {
+						let tri = [indices[0], indices[i], indices[i + 1]];
+						groups[group].triangles.push(tri);
+						let [p0, p1, p2] = tri.map(|i| data.positions[i as usize]);
+						let face_normal = cross(p1 - p0, p2 - p0);
+						for (k, corner) in [0, i, i + 1].into_iter().enumerate() {
+							if corners[corner].2.is_none() {
+								missing_normals.push((tri[k], face_normal));
+							}
+						}
+					}
+				}
+				"usemtl" => {
+					let name = tokens.rest().ok_or_else(|| self.error(line_no, "`usemtl` without a name".to_string()))?;
+					let material = self.materials.get(name).cloned()
+						.ok_or_else(|| self.error(line_no, format!("unknown material `{name}`")))?;
+					current_group = Some(groups.iter().position(|g| g.material_name.as_deref() == Some(name)).unwrap_or_else(|| {
+						groups.push(ObjGroup { material_name: Some(name.to_string()), material, triangles: Vec::new() });
+						groups.len() - 1
+					}));
+				}
+				"mtllib" => {
+					let names = tokens.rest().ok_or_else(|| self.error(line_no, "`mtllib` without a file name".to_string()))?;
+					for name in names.split_whitespace() {
+						self.materials.extend(load_mtl(base_dir.join(name))?);
+					}
+				}
+				// Grouping, lines, points and free-form geometry don't make triangles
+				"o" | "g" | "s" | "mg" | "l" | "p" | "vp" | "cstype" | "deg" | "bmat" | "step" | "curv" | "curv2" | "surf"
+				| "parm" | "trim" | "hole" | "scrv" | "sp" | "end" | "con" | "bevel" | "c_interp" | "d_interp" | "lod"
+				| "usemap" | "maplib" | "shadow_obj" | "trace_obj" | "ctech" | "stech" => {}
+				_ => return Err(self.error(line_no, format!("unknown statement `{keyword}`"))),
+			}
+		}
+
+		if self.colors.iter().all(Option::is_none) {
+			data.colors.clear();
+		} else {
+			for group in groups.iter_mut().filter(|g| g.material_name.is_none()) {
+				group.material = Arc::new(Lambertian::from_texture(Arc::new(VertexColorTexture::new(Color::new(0.8, 0.8, 0.8)))));
+			}
+		}
+		if !any_uvs {
+			data.uvs.clear();
+		}
+		if any_normals {
+			for (vertex, face_normal) in missing_normals {
+				data.normals[vertex as usize] += face_normal;
+			}
+			for n in &mut data.normals {
+				if !n.near_zero() {
+					*n = unit_vector(n);
+				}
+			}
+		} else {
+			data.normals.clear();
+		}
+		groups.retain(|g| !g.triangles.is_empty());
+		Ok(ObjModel { data: Arc::new(data), groups })
+	}
+
+	// `v`, `v/vt`, `v//vn` or `v/vt/vn`, 1-based or negative from the end
+	fn corner(&self, word: &str, line_no: usize) -> Result<Corner, ObjError> {
+		let mut parts = word.split('/');
+		let resolve = |part: Option<&str>, len: usize, what: &str| -> Result<Option<usize>, ObjError> {
+			let Some(part) = part.filter(|p| !p.is_empty()) else {
+				return Ok(None);
+			};
+			let index: i64 = part.parse().map_err(|_| self.error(line_no, format!("invalid {what} index `{part}` in `{word}`")))?;
+			let resolved = if index < 0 { len as i64 + index } else { index - 1 };
+			if index == 0 || resolved < 0 || resolved >= len as i64 {
+				return Err(self.error(line_no, format!("{what} index {index} out of range, {len} defined so far")));
+			}
+			Ok(Some(resolved as usize))
+		};
+		let p = resolve(parts.next(), self.positions.len(), "vertex")?
+			.ok_or_else(|| self.error(line_no, format!("face corner `{word}` has no vertex index")))?;
+		let t = resolve(parts.next(), self.uvs.len(), "texture coordinate")?;
+		let n = resolve(parts.next(), self.normals.len(), "normal")?;
+		if parts.next().is_some() {
+			return Err(self.error(line_no, format!("face corner `{word}` has too many indices")));
+		}
+		Ok((p, t, n))
+	}
+
+	fn error(&self, line: usize, message: String) -> ObjError {
+		ObjError::Parse { path: self.path.to_path_buf(), line, message }
+	}
+}
+
+// Used for faces with no `usemtl`
+fn default_material() -> Arc<dyn Material> {
+	Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)))
+}
+
+// Whitespace separated words of one line, with comments stripped
+struct Tokens<'a> {
+	line: &'a str,
+	words: std::str::SplitWhitespace<'a>,
+	keyword: &'a str,
+	line_no: usize,
+	path: &'a Path,
+}
+
+impl<'a> Tokens<'a> {
+	fn new(line: &'a str, line_no: usize, path: &'a Path) -> Self {
+		let line = line.split_once('#').map_or(line, |(before, _)| before).trim();
+		Tokens { line, words: line.split_whitespace(), keyword: "", line_no, path }
+	}
+
+	fn next_word(&mut self) -> Option<&'a str> {
+		let word = self.words.next()?;
+		if self.keyword.is_empty() {
+			self.keyword = word;
+		}
+		Some(word)
+	}
+
+	// Everything after the keyword, for names that may contain spaces
+	fn rest(&self) -> Option<&'a str> {
+		Some(self.line[self.keyword.len()..].trim()).filter(|rest| !rest.is_empty())
+	}
+
+	fn optional_number<T: std::str::FromStr>(&mut self) -> Result<Option<T>, ObjError> {
+		let Some(word) = self.words.next() else {
+			return Ok(None);
+		};
+		word.parse().map(Some).map_err(|_| ObjError::Parse {
+			path: self.path.to_path_buf(),
+			line: self.line_no,
+			message: format!("invalid number `{word}` in `{}`", self.keyword),
+		})
+	}
+
+	fn number<T: std::str::FromStr>(&mut self) -> Result<T, ObjError> {
+		self.optional_number()?.ok_or_else(|| ObjError::Parse {
+			path: self.path.to_path_buf(),
+			line: self.line_no,
+			message: format!("missing value in `{}`", self.keyword),
+		})
+	}
+
+	fn vec3(&mut self) -> Result<Vec3, ObjError> {
+		Ok(Vec3::new(self.number()?, self.number()?, self.number()?))
+	}
+
+	// A single value means grey
+	fn color(&mut self) -> Result<Color, ObjError> {
+		let r = self.number()?;
+		match self.optional_number()? {
+			Some(g) => Ok(Color::new(r, g, self.number()?)),
+			None => Ok(Color::new(r, r, r)),
+		}
+	}
+}
+
+impl<'a> Iterator for Tokens<'a> {
+	type Item = &'a str;
+
+	fn next(&mut self) -> Option<&'a str> {
+		self.words.next()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{hittable::HitRecord, ray::Ray};
+
+	fn parse(src: &str) -> Result<ObjModel, ObjError> {
+		ObjParser { path: Path::new("test.obj"), positions: Vec::new(), colors: Vec::new(), normals: Vec::new(), uvs: Vec::new(), materials: BTreeMap::new() }.parse(src)
+	}
+
+	// Line and message of a parse error
+	fn parse_error(src: &str) -> (usize, String) {
+		match parse(src) {
+			Err(ObjError::Parse { line, message, .. }) => (line, message),
+			Err(e) => panic!("expected a parse error, got {e}"),
+			Ok(_) => panic!("expected a parse error"),
+		}
+	}
+
+	fn triangles(model: &ObjModel) -> Vec<[u32; 3]> {
+		model.groups.iter().flat_map(|g| g.triangles.iter().copied()).collect()
+	}
+
+	const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+	#[test]
+	fn negative_indices_count_from_the_end() {
+		let positive = parse(&format!("{SQUARE}f 1 2 3 4\n")).ok().unwrap();
+		let negative = parse(&format!("{SQUARE}f -4 -3 -2 -1\n")).ok().unwrap();
+		assert_eq!(triangles(&positive), vec![[0, 1, 2], [0, 2, 3]]);
+		assert_eq!(triangles(&negative), triangles(&positive));
+		let positions = |m: &ObjModel| m.data.positions.iter().map(|p| p.e).collect::<Vec<_>>();
+		assert_eq!(positions(&negative), positions(&positive));
+	}
+
+	#[test]
+	fn negative_indices_are_relative_to_the_line() {
+		// -1 is the last vertex defined before the face, not in the file
+		let model = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 5 5 5\n").ok().unwrap();
+		assert_eq!(model.data.positions.iter().map(|p| p.e).collect::<Vec<_>>(), vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+	}
+
+	#[test]
+	fn corner_variants() {
+		let uvs = "vt 0 0\nvt 1 0\nvt 1 1\n";
+		let normals = "vn 0 0 1\n";
+
+		let plain = parse(&format!("{SQUARE}f 1 2 3\n")).ok().unwrap();
+		assert!(plain.data.uvs.is_empty() && plain.data.normals.is_empty());
+
+		let with_uvs = parse(&format!("{SQUARE}{uvs}f 1/1 2/2 3/3\n")).ok().unwrap();
+		assert_eq!(with_uvs.data.uvs, vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+		assert!(with_uvs.data.normals.is_empty());
+
+		let with_normals = parse(&format!("{SQUARE}{normals}f 1//1 2//1 3//1\n")).ok().unwrap();
+		assert!(with_normals.data.uvs.is_empty());
+		assert!(with_normals.data.normals.iter().all(|n| n.e == [0.0, 0.0, 1.0]));
+
+		let with_both = parse(&format!("{SQUARE}{uvs}{normals}f 1/1/1 2/2/1 3/3/1\n")).ok().unwrap();
+		assert_eq!(with_both.data.uvs.len(), 3);
+		assert_eq!(with_both.data.normals.len(), 3);
+	}
+
+	#[test]
+	fn corners_without_normals_get_the_face_normal() {
+		let model = parse(&format!("{SQUARE}vn 0 0 1\nf 1//1 2 3\n")).ok().unwrap();
+		assert!(model.data.normals.iter().all(|n| n.e == [0.0, 0.0, 1.0]));
+	}
+
+	#[test]
+	fn corners_are_shared_only_when_everything_matches() {
+		let model = parse(&format!("{SQUARE}vt 0 0\nvt 1 1\nf 1/1 2/1 3/1\nf 1/1 3/1 4/1\nf 1/2 2/2 3/2\n")).ok().unwrap();
+		// 4 corners with the first uv, 3 with the second
+		assert_eq!(model.data.positions.len(), 7);
+	}
+
+	#[test]
+	fn errors_name_the_line() {
+		assert_eq!(parse_error(&format!("{SQUARE}\n# comment\nf 1 2 5\n")), (7, "vertex index 5 out of range, 4 defined so far".to_string()));
+		assert_eq!(parse_error(&format!("{SQUARE}f 1 2 3\nf -5 1 2\n")), (6, "vertex index -5 out of range, 4 defined so far".to_string()));
+		assert_eq!(parse_error(&format!("{SQUARE}f 0 1 2\n")), (5, "vertex index 0 out of range, 4 defined so far".to_string()));
+		assert_eq!(parse_error(&format!("{SQUARE}f 1/1 2 3\n")), (5, "texture coordinate index 1 out of range, 0 defined so far".to_string()));
+		assert_eq!(parse_error(&format!("{SQUARE}f 1 2\n")), (5, "face with 2 vertices, needs at least 3".to_string()));
+		assert_eq!(parse_error(&format!("{SQUARE}f 1/// 2 3\n")), (5, "face corner `1///` has too many indices".to_string()));
+		assert_eq!(parse_error("v 0 0 zero\n"), (1, "invalid number `zero` in `v`".to_string()));
+		assert_eq!(parse_error("v 0 0\n"), (1, "missing value in `v`".to_string()));
+		assert_eq!(parse_error("o thing\nbogus 1 2\n"), (2, "unknown statement `bogus`".to_string()));
+		assert_eq!(parse_error(&format!("{SQUARE}usemtl missing\n")), (5, "unknown material `missing`".to_string()));
+	}
+
+	#[test]
+	fn mtl_errors_name_the_mtl_file() {
+		let dir = std::env::temp_dir().join(format!("ray_tracing_obj_test_{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(dir.join("good.mtl"), "newmtl red\nKd 0.8 0.1 0.1\n").unwrap();
+		fs::write(dir.join("bad.mtl"), "# materials\n\nKd 1 1 1\n").unwrap();
+		fs::write(dir.join("good.obj"), format!("mtllib good.mtl\n{SQUARE}usemtl red\nf 1 2 3\n")).unwrap();
+		fs::write(dir.join("bad.obj"), "mtllib bad.mtl\n").unwrap();
+
+		let model = load_obj(dir.join("good.obj")).ok().unwrap();
+		assert_eq!(model.groups.len(), 1);
+		assert_eq!(model.groups[0].material_name.as_deref(), Some("red"));
+		let error = load_obj(dir.join("bad.obj")).err().unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+		match error {
+			ObjError::Parse { path, line, message } => {
+				assert_eq!(path.file_name().unwrap(), "bad.mtl");
+				assert_eq!((line, message.as_str()), (3, "`Kd` before any `newmtl`"));
+			}
+			e => panic!("expected a parse error, got {e}"),
+		}
+	}
+
+	#[test]
+	fn map_file_names() {
+		assert_eq!(map_file_name("wood.png"), Some("wood.png"));
+		assert_eq!(map_file_name("  textures/old wood.png "), Some("textures/old wood.png"));
+		assert_eq!(map_file_name("-s 2 2 1 -o 0.5 -clamp on my map.jpg"), Some("my map.jpg"));
+		// Vector options take one to three numbers
+		assert_eq!(map_file_name("-s 2 old wood.png"), Some("old wood.png"));
+		assert_eq!(map_file_name("-mm 0 1 -bm 0.5 -imfchan l 3.png"), Some("3.png"));
+		assert_eq!(map_file_name("-clamp on"), None);
+	}
+
+	#[test]
+	fn map_kd_with_spaces_is_tinted_by_kd() {
+		let dir = std::env::temp_dir().join(format!("ray_tracing_mtl_map_test_{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		::image::RgbImage::from_pixel(2, 2, ::image::Rgb([255, 255, 255])).save(dir.join("white map.png")).unwrap();
+		fs::write(dir.join("tinted.mtl"), "newmtl tinted\nKd 0.5 0.25 1\nmap_Kd -s 1 1 1 white map.png\n\nnewmtl plain\nmap_Kd white map.png\n").unwrap();
+		let materials = load_mtl(dir.join("tinted.mtl"));
+		fs::remove_dir_all(&dir).unwrap();
+		let materials = materials.unwrap_or_else(|e| panic!("{e}"));
+
+		let albedo = |name: &str| {
+			let mut attenuation = Color::default();
+			let r = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+			let rec = HitRecord { normal: Vec3::new(0.0, 0.0, 1.0), front_face: true, u: 0.5, v: 0.5, ..Default::default() };
+			assert!(materials[name].scatter(&r, &rec, &mut attenuation, &mut Ray::default()));
+			attenuation
+		};
+		assert!((albedo("tinted") - Color::new(0.5, 0.25, 1.0)).near_zero());
+		assert!((albedo("plain") - Color::new(1.0, 1.0, 1.0)).near_zero());
+	}
+}
@@ -0,0 +1,134 @@
+use std::io::{self, Write};
+
+use crate::image::Image;
+
+const MAGIC: u32 = 20000630;
+// Single part scanline file
+const VERSION: u32 = 2;
+
+const PIXEL_TYPE_UINT: i32 = 0;
+const PIXEL_TYPE_FLOAT: i32 = 2;
+
+// One channel of the file, names with a dot prefix are layers
+struct Channel {
+	name: &'static str,
+	pixel_type: i32,
+	values: Vec<[u8; 4]>,
+}
+
+impl Channel {
+	fn float<I: Iterator<Item = f64>>(name: &'static str, values: I) -> Self {
+		Channel { name, pixel_type: PIXEL_TYPE_FLOAT, values: values.map(|v| (v as f32).to_le_bytes()).collect() }
+	}
+	fn uint<I: Iterator<Item = u32>>(name: &'static str, values: I) -> Self {
+		Channel { name, pixel_type: PIXEL_TYPE_UINT, values: values.map(|v| v.to_le_bytes()).collect() }
+	}
+}
+
+// Uncompressed OpenEXR of the unclamped linear radiance, with any AOVs as extra layers
+pub fn write_exr<W: Write>(mut out: W, image: &Image) -> io::Result<()> {
+	// The data window is inclusive, so it can't describe zero pixels
+	if image.width == 0 || image.height == 0 {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("cannot write a {}x{} image as OpenEXR", image.width, image.height)));
+	}
+	let mut channels = vec![
+		Channel::float("R", image.pixels.iter().map(|c| c.x())),
+		Channel::float("G", image.pixels.iter().map(|c| c.y())),
+		Channel::float("B", image.pixels.iter().map(|c| c.z())),
+	];
+	if let Some(aovs) = &image.aovs {
+		channels.extend([
+			Channel::float("Z", aovs.depth.iter().copied()),
+			Channel::float("normal.X", aovs.normal.iter().map(|n| n.x())),
+			Channel::float("normal.Y", aovs.normal.iter().map(|n| n.y())),
+			Channel::float("normal.Z", aovs.normal.iter().map(|n| n.z())),
+			Channel::float("albedo.R", aovs.albedo.iter().map(|c| c.x())),
+			Channel::float("albedo.G", aovs.albedo.iter().map(|c| c.y())),
+			Channel::float("albedo.B", aovs.albedo.iter().map(|c| c.z())),
+			Channel::uint("samples", aovs.samples.iter().copied()),
+		]);
+	}
+	// Readers expect channels in alphabetical order
+	channels.sort_by(|a, b| a.name.cmp(b.name));
+
+	let mut header = Vec::new();
+	header.extend_from_slice(&MAGIC.to_le_bytes());
+	header.extend_from_slice(&VERSION.to_le_bytes());
+
+	let mut chlist = Vec::new();
+	for channel in &channels {
+		chlist.extend_from_slice(channel.name.as_bytes());
+		chlist.push(0);
+		chlist.extend_from_slice(&channel.pixel_type.to_le_bytes());
+		// pLinear and reserved bytes, then x and y sampling
+		chlist.extend_from_slice(&[0, 0, 0, 0]);
+		chlist.extend_from_slice(&1i32.to_le_bytes());
+		chlist.extend_from_slice(&1i32.to_le_bytes());
+	}
+	chlist.push(0);
+
+	let mut window = Vec::new();
+	for v in [0, 0, image.width as i32 - 1, image.height as i32 - 1] {
+		window.extend_from_slice(&v.to_le_bytes());
+	}
+
+	write_attribute(&mut header, "channels", "chlist", &chlist);
+	write_attribute(&mut header, "compression", "compression", &[0]);
+	write_attribute(&mut header, "dataWindow", "box2i", &window);
+	write_attribute(&mut header, "displayWindow", "box2i", &window);
+	write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+	write_attribute(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+	write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+	write_attribute(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes());
+	header.push(0);
+
+	// Every scanline is its own chunk, listed in the offset table after the header
+	let line_size = image.width * channels.len() * 4;
+	let chunk_size = 8 + line_size;
+	let first_chunk = header.len() + image.height * 8;
+	for y in 0..image.height {
+		header.extend_from_slice(&((first_chunk + y * chunk_size) as u64).to_le_bytes());
+	}
+	out.write_all(&header)?;
+
+	let mut line = Vec::with_capacity(chunk_size);
+	for y in 0..image.height {
+		line.clear();
+		line.extend_from_slice(&(y as i32).to_le_bytes());
+		line.extend_from_slice(&(line_size as i32).to_le_bytes());
+		for channel in &channels {
+			for value in &channel.values[y * image.width..(y + 1) * image.width] {
+				line.extend_from_slice(value);
+			}
+		}
+		out.write_all(&line)?;
+	}
+	out.flush()
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+	header.extend_from_slice(name.as_bytes());
+	header.push(0);
+	header.extend_from_slice(kind.as_bytes());
+	header.push(0);
+	header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+	header.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rejects_empty_images() {
+		for (width, height) in [(0, 0), (3, 0), (0, 2)] {
+			let mut out = Vec::new();
+			let err = write_exr(&mut out, &Image::new(width, height)).err().unwrap();
+			assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+			assert!(out.is_empty());
+		}
+		let mut out = Vec::new();
+		write_exr(&mut out, &Image::new(1, 1)).unwrap();
+		assert!(out.starts_with(&MAGIC.to_le_bytes()));
+	}
+}
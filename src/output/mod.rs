@@ -0,0 +1,58 @@
+use std::{fs::File, io::{self, BufWriter, Write}, path::Path};
+
+use crate::{image::Image, tonemap::ToneMapping};
+
+pub mod png;
+pub mod ppm;
+pub mod pfm;
+pub mod exr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+	// 8-bit RGB
+	Png,
+	// Binary P6 PPM
+	Ppm,
+	// Plain text P3 PPM
+	PpmText,
+	// 32-bit float, not clamped or gamma encoded
+	Pfm,
+	// OpenEXR, 32-bit float with any AOV layers
+	Exr,
+}
+
+impl ImageFormat {
+	// Picks the format from the file extension
+	pub fn from_path(path: &Path) -> Option<Self> {
+		let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+		match ext.as_str() {
+			"png" => Some(ImageFormat::Png),
+			"ppm" => Some(ImageFormat::Ppm),
+			"pfm" => Some(ImageFormat::Pfm),
+			"exr" => Some(ImageFormat::Exr),
+			_ => None,
+		}
+	}
+}
+
+// Tone mapping only applies to the 8-bit formats, float formats keep the radiance as is
+pub fn write_image<P: AsRef<Path>>(path: P, image: &Image, tone: &ToneMapping) -> io::Result<()> {
+	let path = path.as_ref();
+	let format = ImageFormat::from_path(path).ok_or_else(|| io::Error::new(
+		io::ErrorKind::InvalidInput,
+		format!("unsupported image format: {}", path.display()),
+	))?;
+	let mut out = BufWriter::new(File::create(path)?);
+	write_format(&mut out, image, format, tone)?;
+	out.flush()
+}
+
+pub fn write_format<W: Write>(out: W, image: &Image, format: ImageFormat, tone: &ToneMapping) -> io::Result<()> {
+	match format {
+		ImageFormat::Png => png::write_png(out, image, tone),
+		ImageFormat::Ppm => ppm::write_ppm_binary(out, image, tone),
+		ImageFormat::PpmText => ppm::write_ppm(out, image, tone),
+		ImageFormat::Pfm => pfm::write_pfm(out, image),
+		ImageFormat::Exr => exr::write_exr(out, image),
+	}
+}
@@ -0,0 +1,21 @@
+use std::io::{self, Write};
+
+use crate::image::Image;
+
+// Portable float map, linear radiance without clamping
+pub fn write_pfm<W: Write>(mut out: W, image: &Image) -> io::Result<()> {
+	// Negative scale means little endian
+	write!(out, "PF\n{} {}\n-1.0\n", image.width, image.height)?;
+	let mut bytes = Vec::with_capacity(image.pixels.len() * 12);
+	// PFM stores rows bottom to top
+	for y in (0..image.height).rev() {
+		for x in 0..image.width {
+			let c = image.get(x, y);
+			for channel in c.e {
+				bytes.extend_from_slice(&(channel as f32).to_le_bytes());
+			}
+		}
+	}
+	out.write_all(&bytes)?;
+	out.flush()
+}
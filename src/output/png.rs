@@ -0,0 +1,53 @@
+use std::io::{self, Write};
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+
+use crate::{image::Image, tonemap::ToneMapping};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// 8-bit RGB PNG
+pub fn write_png<W: Write>(mut out: W, image: &Image, tone: &ToneMapping) -> io::Result<()> {
+	out.write_all(&SIGNATURE)?;
+
+	let mut header = Vec::with_capacity(13);
+	header.extend_from_slice(&(image.width as u32).to_be_bytes());
+	header.extend_from_slice(&(image.height as u32).to_be_bytes());
+	// Bit depth 8, truecolor, deflate, adaptive filtering, no interlace
+	header.extend_from_slice(&[8, 2, 0, 0, 0]);
+	write_chunk(&mut out, b"IHDR", &header)?;
+
+	let stride = image.width * 3;
+	let mut raw = Vec::with_capacity((stride + 1) * image.height);
+	let mut prev_row = vec![0u8; stride];
+	for y in 0..image.height {
+		let row: Vec<u8> = (0..image.width).flat_map(|x| tone.to_bytes(image.get(x, y))).collect();
+		// Up filter, stores the difference to the row above
+		raw.push(2);
+		raw.extend(row.iter().zip(&prev_row).map(|(&cur, &up)| cur.wrapping_sub(up)));
+		prev_row = row;
+	}
+	write_chunk(&mut out, b"IDAT", &compress_to_vec_zlib(&raw, 6))?;
+	write_chunk(&mut out, b"IEND", &[])?;
+	out.flush()
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+	out.write_all(&(data.len() as u32).to_be_bytes())?;
+	out.write_all(kind)?;
+	out.write_all(data)?;
+	let crc = crc32(crc32(0, kind), data);
+	out.write_all(&crc.to_be_bytes())
+}
+
+// CRC-32 as used by PNG chunks, continuing from a previous value
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+	let mut crc = !crc;
+	for &byte in data {
+		crc ^= byte as u32;
+		for _ in 0..8 {
+			crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+		}
+	}
+	!crc
+}
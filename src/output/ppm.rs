@@ -0,0 +1,21 @@
+use std::io::{self, Write};
+
+use crate::{image::Image, tonemap::ToneMapping};
+
+// Plain text P3 PPM
+pub fn write_ppm<W: Write>(mut out: W, image: &Image, tone: &ToneMapping) -> io::Result<()> {
+	writeln!(out, "P3\n{} {}\n255", image.width, image.height)?;
+	for &pixel_color in &image.pixels {
+		let [rbyte, gbyte, bbyte] = tone.to_bytes(pixel_color);
+		writeln!(out, "{rbyte} {gbyte} {bbyte}")?;
+	}
+	out.flush()
+}
+
+// Binary P6 PPM
+pub fn write_ppm_binary<W: Write>(mut out: W, image: &Image, tone: &ToneMapping) -> io::Result<()> {
+	write!(out, "P6\n{} {}\n255\n", image.width, image.height)?;
+	let bytes: Vec<u8> = image.pixels.iter().flat_map(|&c| tone.to_bytes(c)).collect();
+	out.write_all(&bytes)?;
+	out.flush()
+}
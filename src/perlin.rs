@@ -0,0 +1,84 @@
+use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
+
+use crate::vec3::Point3;
+
+// Ken Perlin's improved noise, the permutation comes from a seed so results are reproducible
+pub struct Perlin {
+	perm: [u8; 512],
+}
+
+impl Perlin {
+	pub fn new(seed: u64) -> Self {
+		let mut table: Vec<u8> = (0..=255).collect();
+		table.shuffle(&mut SmallRng::seed_from_u64(seed));
+		let mut perm = [0; 512];
+		for (i, p) in perm.iter_mut().enumerate() {
+			*p = table[i & 255];
+		}
+		Perlin { perm }
+	}
+
+	// Smooth noise in roughly [-1, 1], zero at every integer lattice point
+	pub fn noise(&self, p: &Point3) -> f64 {
+		let (fx, fy, fz) = (p.x().floor(), p.y().floor(), p.z().floor());
+		let xi = (fx as i64 & 255) as usize;
+		let yi = (fy as i64 & 255) as usize;
+		let zi = (fz as i64 & 255) as usize;
+		let (x, y, z) = (p.x() - fx, p.y() - fy, p.z() - fz);
+		let (u, v, w) = (fade(x), fade(y), fade(z));
+
+		let perm = &self.perm;
+		let a = perm[xi] as usize + yi;
+		let aa = perm[a] as usize + zi;
+		let ab = perm[a + 1] as usize + zi;
+		let b = perm[xi + 1] as usize + yi;
+		let ba = perm[b] as usize + zi;
+		let bb = perm[b + 1] as usize + zi;
+
+		lerp(w,
+			lerp(v,
+				lerp(u, grad(perm[aa], x, y, z), grad(perm[ba], x - 1.0, y, z)),
+				lerp(u, grad(perm[ab], x, y - 1.0, z), grad(perm[bb], x - 1.0, y - 1.0, z))),
+			lerp(v,
+				lerp(u, grad(perm[aa + 1], x, y, z - 1.0), grad(perm[ba + 1], x - 1.0, y, z - 1.0)),
+				lerp(u, grad(perm[ab + 1], x, y - 1.0, z - 1.0), grad(perm[bb + 1], x - 1.0, y - 1.0, z - 1.0))))
+	}
+
+	// Sum of absolute octaves, every octave `lacunarity` times the frequency and `gain` times the amplitude
+	pub fn turbulence(&self, p: &Point3, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+		self.octaves(p, octaves, lacunarity, gain, |n| n.abs())
+	}
+
+	// Fractional Brownian motion, like turbulence but the octaves keep their sign
+	pub fn fbm(&self, p: &Point3, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+		self.octaves(p, octaves, lacunarity, gain, |n| n)
+	}
+
+	fn octaves<F: Fn(f64) -> f64>(&self, p: &Point3, octaves: u32, lacunarity: f64, gain: f64, f: F) -> f64 {
+		let mut sum = 0.0;
+		let mut temp_p = *p;
+		let mut weight = 1.0;
+		for _ in 0..octaves {
+			sum += weight * f(self.noise(&temp_p));
+			weight *= gain;
+			temp_p *= lacunarity;
+		}
+		sum
+	}
+}
+
+fn fade(t: f64) -> f64 {
+	t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+	a + t * (b - a)
+}
+
+// Dot product with one of twelve gradient directions picked by the hash
+fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+	let h = hash & 15;
+	let u = if h < 8 { x } else { y };
+	let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+	(if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, ray::Ray, vec3::{dot, orthonormal_basis, unit_vector, Point3, Vec3}};
+
+// Infinite plane through a point, BvhNode keeps it out of the tree
+pub struct Plane {
+	point: Point3,
+	normal: Vec3,
+	tangent: Vec3,
+	bitangent: Vec3,
+	mat: Arc<dyn Material>,
+	bbox: Aabb,
+}
+
+impl Plane {
+	pub fn new(point: Point3, normal: Vec3, mat: Arc<dyn Material>) -> Self {
+		let normal = unit_vector(&normal);
+		let (tangent, bitangent) = orthonormal_basis(&normal);
+
+		// Only bounded along an axis the plane is perpendicular to
+		let axis = |i: usize| {
+			if (0..3).all(|j| j == i || normal[j] == 0.0) {
+				Interval::new(point[i], point[i])
+			} else {
+				Interval::UNIVERSE
+			}
+		};
+		let bbox = Aabb::from_intervals(axis(0), axis(1), axis(2));
+		Plane { point, normal, tangent, bitangent, mat, bbox }
+	}
+}
+
+impl Hittable for Plane {
+	fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+		let denom = dot(self.normal, *r.dir());
+
+		// Parallel to the plane
+		if denom.abs() < 1e-8 {
+			return false;
+		}
+
+		let t = dot(self.normal, self.point - *r.origin()) / denom;
+		if !ray_t.contains(t) {
+			return false;
+		}
+
+		rec.t = t;
+		rec.p = r.at(t);
+		// Distances along the plane axes, textures have to tile them
+		let offset = rec.p - self.point;
+		(rec.u, rec.v) = (dot(offset, self.tangent), dot(offset, self.bitangent));
+		rec.vertex_color = None;
+		rec.mat = self.mat.clone();
+		rec.set_face_normal(r, &self.normal);
+
+		true
+	}
+
+	fn bounding_box(&self) -> Aabb {
+		self.bbox
+	}
+}
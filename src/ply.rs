@@ -0,0 +1,414 @@
+use std::{fmt::Display, fs, io, path::{Path, PathBuf}, str::SplitAsciiWhitespace};
+
+use crate::{color::{srgb_to_linear, Color}, mesh::{LoadedMesh, MeshData}, vec3::{Point3, Vec3}};
+
+#[derive(Debug)]
+pub enum PlyError {
+	Io { path: PathBuf, source: io::Error },
+	Parse { path: PathBuf, message: String },
+}
+
+impl Display for PlyError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			PlyError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+			PlyError::Parse { path, message } => write!(f, "{}: {message}", path.display()),
+		}
+	}
+}
+
+impl std::error::Error for PlyError {}
+
+// Reads ASCII and little or big endian binary PLY. Vertex positions are required;
+// normals, texture coordinates and colors are picked up when present.
+pub fn load_ply<P: AsRef<Path>>(path: P) -> Result<LoadedMesh, PlyError> {
+	let path = path.as_ref();
+	let bytes = fs::read(path).map_err(|source| PlyError::Io { path: path.to_path_buf(), source })?;
+	parse_ply(&bytes).map_err(|message| PlyError::Parse { path: path.to_path_buf(), message })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+	Ascii,
+	BinaryLittleEndian,
+	BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ScalarType {
+	I8,
+	U8,
+	I16,
+	U16,
+	I32,
+	U32,
+	F32,
+	F64,
+}
+
+impl ScalarType {
+	fn from_name(name: &str) -> Option<Self> {
+		Some(match name {
+			"char" | "int8" => ScalarType::I8,
+			"uchar" | "uint8" => ScalarType::U8,
+			"short" | "int16" => ScalarType::I16,
+			"ushort" | "uint16" => ScalarType::U16,
+			"int" | "int32" => ScalarType::I32,
+			"uint" | "uint32" => ScalarType::U32,
+			"float" | "float32" => ScalarType::F32,
+			"double" | "float64" => ScalarType::F64,
+			_ => return None,
+		})
+	}
+
+	fn size(self) -> usize {
+		match self {
+			ScalarType::I8 | ScalarType::U8 => 1,
+			ScalarType::I16 | ScalarType::U16 => 2,
+			ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+			ScalarType::F64 => 8,
+		}
+	}
+
+	// What a full-intensity color channel is stored as
+	fn color_scale(self) -> f64 {
+		match self {
+			ScalarType::U8 => 255.0,
+			ScalarType::U16 => 65535.0,
+			_ => 1.0,
+		}
+	}
+}
+
+enum Property {
+	Scalar { name: String, ty: ScalarType },
+	List { name: String, count_ty: ScalarType, item_ty: ScalarType },
+}
+
+impl Property {
+	fn name(&self) -> &str {
+		match self {
+			Property::Scalar { name, .. } | Property::List { name, .. } => name,
+		}
+	}
+}
+
+struct Element {
+	name: String,
+	count: usize,
+	properties: Vec<Property>,
+}
+
+fn parse_ply(bytes: &[u8]) -> Result<LoadedMesh, String> {
+	let (format, elements, body) = parse_header(bytes)?;
+	let mut reader = match format {
+		Format::Ascii => {
+			let text = std::str::from_utf8(body).map_err(|_| "ASCII body is not valid text".to_string())?;
+			Reader::Ascii(text.split_ascii_whitespace())
+		}
+		Format::BinaryLittleEndian => Reader::Binary { data: body, pos: 0, big_endian: false },
+		Format::BinaryBigEndian => Reader::Binary { data: body, pos: 0, big_endian: true },
+	};
+
+	let vertex_count = elements.iter().find(|e| e.name == "vertex").map(|e| e.count)
+		.ok_or_else(|| "no vertex element".to_string())?;
+	let mut data = MeshData::default();
+	let mut triangles = Vec::new();
+	for element in &elements {
+		match element.name.as_str() {
+			"vertex" => read_vertices(&mut reader, element, &mut data)?,
+			"face" => read_faces(&mut reader, element, vertex_count, &mut triangles)?,
+			// Edges, materials and anything else are skipped
+			_ => {
+				for _ in 0..element.count {
+					reader.skip_element(element)?;
+				}
+			}
+		}
+	}
+	Ok(LoadedMesh { data, triangles })
+}
+
+fn parse_header(bytes: &[u8]) -> Result<(Format, Vec<Element>, &[u8]), String> {
+	let mut format = None;
+	let mut elements: Vec<Element> = Vec::new();
+	let mut pos = 0;
+	let mut line_no = 0;
+
+	loop {
+		let end = bytes[pos..].iter().position(|&b| b == b'\n').map(|i| pos + i)
+			.ok_or_else(|| "header has no `end_header`".to_string())?;
+		let line = std::str::from_utf8(&bytes[pos..end]).map_err(|_| format!("header line {} is not valid text", line_no + 1))?.trim();
+		pos = end + 1;
+		line_no += 1;
+		let err = |message: String| format!("header line {line_no}: {message}");
+
+		let words: Vec<&str> = line.split_whitespace().collect();
+		if line_no == 1 {
+			if line != "ply" {
+				return Err("not a PLY file, the first line has to be `ply`".to_string());
+			}
+			continue;
+		}
+		match words.as_slice() {
+			[] => {}
+			["comment" | "obj_info", ..] => {}
+			["format", name, _version] => {
+				format = Some(match *name {
+					"ascii" => Format::Ascii,
+					"binary_little_endian" => Format::BinaryLittleEndian,
+					"binary_big_endian" => Format::BinaryBigEndian,
+					other => return Err(err(format!("unknown format `{other}`"))),
+				});
+			}
+			["element", name, count] => {
+				let count = count.parse().map_err(|_| err(format!("invalid element count `{count}`")))?;
+				elements.push(Element { name: name.to_string(), count, properties: Vec::new() });
+			}
+			["property", "list", count_ty, item_ty, name] => {
+				let element = elements.last_mut().ok_or_else(|| err("property before any element".to_string()))?;
+				let count_ty = ScalarType::from_name(count_ty).ok_or_else(|| err(format!("unknown type `{count_ty}`")))?;
+				if matches!(count_ty, ScalarType::F32 | ScalarType::F64) {
+					return Err(err("list counts have to be integers".to_string()));
+				}
+				let item_ty = ScalarType::from_name(item_ty).ok_or_else(|| err(format!("unknown type `{item_ty}`")))?;
+				element.properties.push(Property::List { name: name.to_string(), count_ty, item_ty });
+			}
+			["property", ty, name] => {
+				let element = elements.last_mut().ok_or_else(|| err("property before any element".to_string()))?;
+				let ty = ScalarType::from_name(ty).ok_or_else(|| err(format!("unknown type `{ty}`")))?;
+				element.properties.push(Property::Scalar { name: name.to_string(), ty });
+			}
+			["end_header"] => break,
+			_ => return Err(err(format!("cannot parse `{line}`"))),
+		}
+	}
+
+	let format = format.ok_or_else(|| "header has no `format` line".to_string())?;
+	Ok((format, elements, &bytes[pos..]))
+}
+
+fn read_vertices(reader: &mut Reader, element: &Element, data: &mut MeshData) -> Result<(), String> {
+	let find = |names: &[&str]| element.properties.iter().position(|p| names.contains(&p.name()));
+	let scalar_type = |index: usize| match element.properties[index] {
+		Property::Scalar { ty, .. } => ty,
+		Property::List { .. } => ScalarType::F64,
+	};
+
+	let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+	let [Some(x), Some(y), Some(z)] = position else {
+		return Err("vertex element needs x, y and z properties".to_string());
+	};
+	let normal = match [find(&["nx"]), find(&["ny"]), find(&["nz"])] {
+		[Some(nx), Some(ny), Some(nz)] => Some([nx, ny, nz]),
+		_ => None,
+	};
+	let uv = match [find(&["u", "s", "texture_u", "texture_s"]), find(&["v", "t", "texture_v", "texture_t"])] {
+		[Some(u), Some(v)] => Some([u, v]),
+		_ => None,
+	};
+	let color = match [find(&["red", "r", "diffuse_red"]), find(&["green", "g", "diffuse_green"]), find(&["blue", "b", "diffuse_blue"])] {
+		[Some(r), Some(g), Some(b)] => Some([r, g, b]),
+		_ => None,
+	};
+
+	let mut values = vec![0.0; element.properties.len()];
+	for vertex in 0..element.count {
+		for (value, property) in values.iter_mut().zip(&element.properties) {
+			*value = match property {
+				Property::Scalar { ty, .. } => reader.read(*ty),
+				Property::List { count_ty, item_ty, .. } => reader.skip_list(*count_ty, *item_ty).map(|_| 0.0),
+			}.map_err(|e| format!("vertex {vertex}: {e}"))?;
+		}
+		data.positions.push(Point3::new(values[x], values[y], values[z]));
+		if let Some([nx, ny, nz]) = normal {
+			data.normals.push(Vec3::new(values[nx], values[ny], values[nz]));
+		}
+		if let Some([u, v]) = uv {
+			data.uvs.push((values[u], values[v]));
+		}
+		if let Some(channels) = color {
+			let [r, g, b] = channels.map(|c| srgb_to_linear(values[c] / scalar_type(c).color_scale()));
+			data.colors.push(Color::new(r, g, b));
+		}
+	}
+	Ok(())
+}
+
+fn read_faces(reader: &mut Reader, element: &Element, vertex_count: usize, triangles: &mut Vec<[u32; 3]>) -> Result<(), String> {
+	if !element.properties.iter().any(|p| matches!(p, Property::List { name, .. } if name == "vertex_indices" || name == "vertex_index")) {
+		return Err("face element needs a vertex_indices list".to_string());
+	}
+
+	let mut indices = Vec::new();
+	for face in 0..element.count {
+		for property in &element.properties {
+			match property {
+				Property::List { name, count_ty, item_ty } if name == "vertex_indices" || name == "vertex_index" => {
+					let count = reader.read_count(*count_ty).map_err(|e| format!("face {face}: {e}"))?;
+					indices.clear();
+					for _ in 0..count {
+						let index = reader.read(*item_ty).map_err(|e| format!("face {face}: {e}"))?;
+						if index < 0.0 || index as usize >= vertex_count {
+							return Err(format!("face {face}: vertex index {index} out of range for {vertex_count} vertices"));
+						}
+						indices.push(index as u32);
+					}
+				}
+				Property::List { count_ty, item_ty, .. } => reader.skip_list(*count_ty, *item_ty).map_err(|e| format!("face {face}: {e}"))?,
+				Property::Scalar { ty, .. } => {
+					reader.read(*ty).map_err(|e| format!("face {face}: {e}"))?;
+				}
+			}
+		}
+		if indices.len() < 3 {
+			return Err(format!("face {face} has {} vertices, needs at least 3", indices.len()));
+		}
+		// Fan out polygons from the first vertex
+		for i in 1..indices.len() - 1 {
+			triangles.push([indices[0], indices[i], indices[i + 1]]);
+		}
+	}
+	Ok(())
+}
+
+// Values of the body one at a time, whatever its encoding
+enum Reader<'a> {
+	Ascii(SplitAsciiWhitespace<'a>),
+	Binary { data: &'a [u8], pos: usize, big_endian: bool },
+}
+
+impl Reader<'_> {
+	fn read(&mut self, ty: ScalarType) -> Result<f64, String> {
+		match self {
+			Reader::Ascii(words) => {
+				let word = words.next().ok_or_else(|| "file ends early".to_string())?;
+				word.parse().map_err(|_| format!("invalid number `{word}`"))
+			}
+			Reader::Binary { data, pos, big_endian } => {
+				let size = ty.size();
+				let bytes = data.get(*pos..*pos + size).ok_or_else(|| "file ends early".to_string())?;
+				*pos += size;
+				let mut buf = [0u8; 8];
+				buf[..size].copy_from_slice(bytes);
+				if *big_endian {
+					buf[..size].reverse();
+				}
+				Ok(match ty {
+					ScalarType::I8 => buf[0] as i8 as f64,
+					ScalarType::U8 => buf[0] as f64,
+					ScalarType::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+					ScalarType::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+					ScalarType::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+					ScalarType::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+					ScalarType::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+					ScalarType::F64 => f64::from_le_bytes(buf),
+				})
+			}
+		}
+	}
+
+	fn read_count(&mut self, ty: ScalarType) -> Result<usize, String> {
+		let count = self.read(ty)?;
+		if count < 0.0 || count.fract() != 0.0 {
+			return Err(format!("invalid list length {count}"));
+		}
+		Ok(count as usize)
+	}
+
+	fn skip_list(&mut self, count_ty: ScalarType, item_ty: ScalarType) -> Result<(), String> {
+		let count = self.read_count(count_ty)?;
+		for _ in 0..count {
+			self.read(item_ty)?;
+		}
+		Ok(())
+	}
+
+	fn skip_element(&mut self, element: &Element) -> Result<(), String> {
+		for property in &element.properties {
+			match property {
+				Property::Scalar { ty, .. } => {
+					self.read(*ty).map_err(|e| format!("{}: {e}", element.name))?;
+				}
+				Property::List { count_ty, item_ty, .. } => self.skip_list(*count_ty, *item_ty).map_err(|e| format!("{}: {e}", element.name))?,
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// A unit square as one quad, with a color per corner
+	const POSITIONS: [[f32; 3]; 4] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+	const COLORS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+
+	fn header(format: &str) -> String {
+		format!("ply\nformat {format} 1.0\ncomment made in memory\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+			property uchar red\nproperty uchar green\nproperty uchar blue\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n")
+	}
+
+	fn ascii() -> Vec<u8> {
+		let mut text = header("ascii");
+		for (p, c) in POSITIONS.iter().zip(COLORS) {
+			text.push_str(&format!("{} {} {} {} {} {}\n", p[0], p[1], p[2], c[0], c[1], c[2]));
+		}
+		text.push_str("4 0 1 2 3\n");
+		text.into_bytes()
+	}
+
+	fn binary(big_endian: bool) -> Vec<u8> {
+		let mut bytes = header(if big_endian { "binary_big_endian" } else { "binary_little_endian" }).into_bytes();
+		for (p, c) in POSITIONS.iter().zip(COLORS) {
+			for f in p {
+				bytes.extend(if big_endian { f.to_be_bytes() } else { f.to_le_bytes() });
+			}
+			bytes.extend(c);
+		}
+		bytes.push(4);
+		for i in 0..4i32 {
+			bytes.extend(if big_endian { i.to_be_bytes() } else { i.to_le_bytes() });
+		}
+		bytes
+	}
+
+	fn check(mesh: &LoadedMesh) {
+		let positions: Vec<[f64; 3]> = mesh.data.positions.iter().map(|p| p.e).collect();
+		assert_eq!(positions, POSITIONS.map(|p| p.map(f64::from)));
+		// The quad fans out into two triangles
+		assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+		let colors: Vec<[f64; 3]> = mesh.data.colors.iter().map(|c| c.e).collect();
+		assert_eq!(colors, vec![[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 1.0, 1.0]]);
+		assert!(mesh.data.normals.is_empty() && mesh.data.uvs.is_empty());
+	}
+
+	#[test]
+	fn ascii_round_trip() {
+		check(&parse_ply(&ascii()).unwrap());
+	}
+
+	#[test]
+	fn binary_little_endian_round_trip() {
+		check(&parse_ply(&binary(false)).unwrap());
+	}
+
+	#[test]
+	fn binary_big_endian_round_trip() {
+		check(&parse_ply(&binary(true)).unwrap());
+	}
+
+	#[test]
+	fn truncated_body() {
+		let mut bytes = binary(true);
+		bytes.truncate(bytes.len() - 2);
+		assert_eq!(parse_ply(&bytes).err().unwrap(), "face 0: file ends early");
+	}
+
+	#[test]
+	fn index_out_of_range() {
+		let bytes = String::from_utf8(ascii()).unwrap().replace("4 0 1 2 3", "3 0 1 4").into_bytes();
+		assert_eq!(parse_ply(&bytes).err().unwrap(), "face 0: vertex index 4 out of range for 4 vertices");
+	}
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, hittable_list::HittableList, interval::Interval, material::Material, ray::Ray, vec3::{cross, dot, unit_vector, Point3, Vec3}};
+
+// Parallelogram with corner q and edges u and v
+pub struct Quad {
+	q: Point3,
+	u: Vec3,
+	v: Vec3,
+	// Turns a point on the plane into (alpha, beta) along u and v
+	w: Vec3,
+	mat: Arc<dyn Material>,
+	bbox: Aabb,
+	normal: Vec3,
+	d: f64,
+}
+
+impl Quad {
+	pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material>) -> Self {
+		let n = cross(u, v);
+		let normal = unit_vector(&n);
+		let d = dot(normal, q);
+		let w = n / dot(n, n);
+
+		let bbox_diagonal1 = Aabb::from_points(q, q + u + v);
+		let bbox_diagonal2 = Aabb::from_points(q + u, q + v);
+		let bbox = Aabb::from_boxes(&bbox_diagonal1, &bbox_diagonal2);
+		Quad { q, u, v, w, mat, bbox, normal, d }
+	}
+}
+
+impl Hittable for Quad {
+	fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+		let denom = dot(self.normal, *r.dir());
+
+		// Parallel to the plane
+		if denom.abs() < 1e-8 {
+			return false;
+		}
+
+		let t = (self.d - dot(self.normal, *r.origin())) / denom;
+		if !ray_t.contains(t) {
+			return false;
+		}
+
+		let intersection = r.at(t);
+		let planar_hitpt_vector = intersection - self.q;
+		let alpha = dot(self.w, cross(planar_hitpt_vector, self.v));
+		let beta = dot(self.w, cross(self.u, planar_hitpt_vector));
+
+		let unit = Interval::new(0.0, 1.0);
+		if !unit.contains(alpha) || !unit.contains(beta) {
+			return false;
+		}
+
+		rec.t = t;
+		rec.p = intersection;
+		(rec.u, rec.v) = (alpha, beta);
+		rec.vertex_color = None;
+		rec.mat = self.mat.clone();
+		rec.set_face_normal(r, &self.normal);
+
+		true
+	}
+
+	fn bounding_box(&self) -> Aabb {
+		self.bbox
+	}
+}
+
+// Six quads facing outwards around the box with opposite corners a and b
+pub fn make_box(a: Point3, b: Point3, mat: Arc<dyn Material>) -> HittableList {
+	let mut sides = HittableList::default();
+
+	let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+	let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+	let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+	let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+	let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+	sides.add(Arc::new(Quad::new(Point3::new(min.x(), min.y(), max.z()), dx, dy, mat.clone()))); // front
+	sides.add(Arc::new(Quad::new(Point3::new(max.x(), min.y(), max.z()), -dz, dy, mat.clone()))); // right
+	sides.add(Arc::new(Quad::new(Point3::new(max.x(), min.y(), min.z()), -dx, dy, mat.clone()))); // back
+	sides.add(Arc::new(Quad::new(Point3::new(min.x(), min.y(), min.z()), dz, dy, mat.clone()))); // left
+	sides.add(Arc::new(Quad::new(Point3::new(min.x(), max.y(), max.z()), dx, -dz, mat.clone()))); // top
+	sides.add(Arc::new(Quad::new(Point3::new(min.x(), min.y(), min.z()), dx, dz, mat))); // bottom
+
+	sides
+}
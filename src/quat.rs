@@ -0,0 +1,106 @@
+use std::ops::Mul;
+
+use crate::{mat4::Mat4, vec3::{unit_vector, Vec3}};
+
+// Rotation as the unit quaternion x i + y j + z k + w
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quat {
+	pub x: f64,
+	pub y: f64,
+	pub z: f64,
+	pub w: f64,
+}
+
+impl Default for Quat {
+	fn default() -> Self {
+		Quat::IDENTITY
+	}
+}
+
+impl Quat {
+	pub const IDENTITY: Quat = Quat { x: 0.0, y: 0.0, z: 0.0, w: 1.0 };
+
+	pub fn new(x: f64, y: f64, z: f64, w: f64) -> Self {
+		Quat { x, y, z, w }.normalized()
+	}
+
+	// Counterclockwise looking down the axis, like the Mat4 rotations
+	pub fn from_axis_angle(axis: Vec3, degrees: f64) -> Self {
+		let axis = unit_vector(&axis);
+		let (sin, cos) = (0.5 * degrees.to_radians()).sin_cos();
+		Quat { x: axis.x() * sin, y: axis.y() * sin, z: axis.z() * sin, w: cos }
+	}
+
+	// Degrees around x, then y, then z
+	pub fn from_euler(x: f64, y: f64, z: f64) -> Self {
+		Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), z)
+			* Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), y)
+			* Quat::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), x)
+	}
+
+	pub fn dot(&self, other: &Quat) -> f64 {
+		self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+	}
+
+	pub fn normalized(&self) -> Quat {
+		let len = self.dot(self).sqrt();
+		if len == 0.0 {
+			return Quat::IDENTITY;
+		}
+		Quat { x: self.x / len, y: self.y / len, z: self.z / len, w: self.w / len }
+	}
+
+	pub fn conjugate(&self) -> Quat {
+		Quat { x: -self.x, y: -self.y, z: -self.z, w: self.w }
+	}
+
+	// Angle in radians between the two rotations, at most pi
+	pub fn angle_to(&self, other: &Quat) -> f64 {
+		2.0 * self.dot(other).abs().min(1.0).acos()
+	}
+
+	// Constant speed along the shorter arc, t = 0 gives a and t = 1 gives b
+	pub fn slerp(a: &Quat, b: &Quat, t: f64) -> Quat {
+		let mut cos = a.dot(b);
+		// q and -q are the same rotation, pick the one closer to a
+		let b = if cos < 0.0 {
+			cos = -cos;
+			Quat { x: -b.x, y: -b.y, z: -b.z, w: -b.w }
+		} else {
+			*b
+		};
+
+		// Nearly the same rotation, where the sine below loses precision
+		let (wa, wb) = if cos > 0.9995 {
+			(1.0 - t, t)
+		} else {
+			let theta = cos.acos();
+			let sin = theta.sin();
+			(((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+		};
+		Quat {
+			x: wa * a.x + wb * b.x,
+			y: wa * a.y + wb * b.y,
+			z: wa * a.z + wb * b.z,
+			w: wa * a.w + wb * b.w,
+		}.normalized()
+	}
+
+	pub fn to_mat4(&self) -> Mat4 {
+		Mat4::from_quaternion(self.x, self.y, self.z, self.w)
+	}
+}
+
+// a * b rotates by b first
+impl Mul<Quat> for Quat {
+	type Output = Quat;
+
+	fn mul(self, rhs: Quat) -> Self::Output {
+		Quat {
+			x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+			y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+			z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+			w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+		}
+	}
+}
@@ -0,0 +1,31 @@
+use std::cell::RefCell;
+
+use rand::{distributions::uniform::SampleRange, rngs::SmallRng, Rng, SeedableRng};
+
+// Every thread has its own generator so renders can be reproduced from a seed
+thread_local! {
+	static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+pub fn seed(seed: u64) {
+	RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+// Seeds from the pixel position so a pixel comes out the same no matter which thread renders it
+pub fn seed_pixel(seed: u64, i: i32, j: i32) {
+	let mut h = seed ^ 0x9e37_79b9_7f4a_7c15;
+	for x in [i as u32 as u64, j as u32 as u64] {
+		h = (h ^ x).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		h ^= h >> 31;
+	}
+	self::seed(h);
+}
+
+// Random number in [0, 1)
+pub fn random_double() -> f64 {
+	RNG.with(|rng| rng.borrow_mut().gen())
+}
+
+pub fn random_range<R: SampleRange<f64>>(range: R) -> f64 {
+	RNG.with(|rng| rng.borrow_mut().gen_range(range))
+}
@@ -0,0 +1,1044 @@
+use std::{collections::BTreeMap, fmt::Display, fs, io, ops::Range, path::{Path, PathBuf}, sync::Arc};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use toml::Spanned;
+
+use crate::{aabb::Aabb, camera::{Background, Camera, ShutterCurve}, color::Color, constant_medium::ConstantMedium, csg::{Csg, CsgOp}, hittable::Hittable, hittable_list::HittableList, disk::Disk, instance::{Instance, Keyframe, MovingInstance}, mat4::Mat4, gltf::{load_gltf, GltfError}, material::{Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Material, Metal}, mesh::{MeshData, TriangleMesh}, obj::load_obj, plane::Plane, ply::load_ply, quat::Quat, quad::{make_box, Quad}, sdf::{BoxSdf, CapsuleSdf, Repeat, RoundedBoxSdf, Sdf, SdfHittable, SmoothUnion, SphereSdf, TorusSdf}, sphere::Sphere, stl::load_stl, texture::{CheckerTexture, ImageTexture, NoisePattern, NoiseTexture, SolidColor, Texture, VertexColorTexture}, triangle::Triangle, vec3::{Point3, Vec3}, volume::{DensityGrid, GridTexture, GridVolume}};
+
+// Everything a scene file describes
+pub struct Scene {
+	pub world: HittableList,
+	pub camera: Camera,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+	Io { path: PathBuf, source: io::Error },
+	// Line is 1-based, context is the offending line of the file
+	Parse { line: usize, message: String, context: String },
+	// A .gltf or .glb given as the whole scene
+	Gltf(GltfError),
+}
+
+impl Display for SceneError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+			SceneError::Parse { line, message, context } => write!(f, "line {line}: {message}\n    {context}"),
+			SceneError::Gltf(e) => e.fmt(f),
+		}
+	}
+}
+
+impl std::error::Error for SceneError {}
+
+impl SceneError {
+	fn at(src: &str, offset: usize, message: String) -> Self {
+		let offset = offset.min(src.len());
+		let line = src[..offset].matches('\n').count() + 1;
+		let context = src.lines().nth(line - 1).unwrap_or_default().trim().to_string();
+		SceneError::Parse { line, message, context }
+	}
+}
+
+// Textures, materials and objects are parsed on their own once their type is known,
+// so every error can point at the exact line
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct SceneDef {
+	#[serde(default)]
+	camera: CameraDef,
+	background: Option<Spanned<toml::Table>>,
+	#[serde(default)]
+	textures: BTreeMap<String, Spanned<toml::Table>>,
+	#[serde(default)]
+	materials: BTreeMap<String, Spanned<toml::Table>>,
+	// Objects that are only used by name, like the parts of a csg object
+	#[serde(default)]
+	shapes: BTreeMap<String, Spanned<toml::Table>>,
+	#[serde(default)]
+	objects: Vec<Spanned<toml::Table>>,
+}
+
+// Anything left out keeps the Camera::new default
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CameraDef {
+	aspect_ratio: Option<f64>,
+	image_width: Option<i32>,
+	samples_per_pixel: Option<i32>,
+	max_depth: Option<i32>,
+	fov: Option<f64>,
+	look_from: Option<[f64; 3]>,
+	look_at: Option<[f64; 3]>,
+	vup: Option<[f64; 3]>,
+	defocus_angle: Option<f64>,
+	focus_dist: Option<f64>,
+	shutter_open: Option<Spanned<f64>>,
+	shutter_close: Option<Spanned<f64>>,
+	shutter_curve: Option<Spanned<ShutterCurveDef>>,
+}
+
+// "box", "triangle" or a list of weights
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ShutterCurveDef {
+	Named(String),
+	Weights(Vec<f64>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SolidDef {
+	color: [f64; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CheckerDef {
+	scale: f64,
+	even: [f64; 3],
+	odd: [f64; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ImageDef {
+	// Relative to the scene file
+	path: Spanned<String>,
+}
+
+// Reads mesh vertex colors, fallback elsewhere
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VertexColorDef {
+	fallback: Option<[f64; 3]>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoiseDef {
+	#[serde(default)]
+	seed: u64,
+	pattern: Spanned<String>,
+	frequency: f64,
+	octaves: Option<u32>,
+	lacunarity: Option<f64>,
+	gain: Option<f64>,
+	low: Option<[f64; 3]>,
+	high: Option<[f64; 3]>,
+}
+
+// Either a plain color or the name of a texture
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LambertianDef {
+	albedo: Option<[f64; 3]>,
+	texture: Option<Spanned<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MetalDef {
+	albedo: Option<[f64; 3]>,
+	texture: Option<Spanned<String>>,
+	#[serde(default)]
+	fuzz: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DielectricDef {
+	refraction_index: f64,
+}
+
+// Volume phase function, emission is given off by what isn't scattered
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HenyeyGreensteinDef {
+	albedo: Option<[f64; 3]>,
+	texture: Option<Spanned<String>>,
+	#[serde(default)]
+	g: f64,
+	emission: Option<[f64; 3]>,
+	emission_texture: Option<Spanned<String>>,
+}
+
+// Emitted radiance, either a plain color or the name of a texture
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiffuseLightDef {
+	emit: Option<[f64; 3]>,
+	texture: Option<Spanned<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GradientDef {
+	bottom: [f64; 3],
+	top: [f64; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDef {
+	texture: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereDef {
+	center: [f64; 3],
+	// Where the center is at time 1, for motion blur
+	center2: Option<[f64; 3]>,
+	radius: f64,
+	material: Spanned<String>,
+}
+
+// Density grid filling the box from a to b, read from a .nrrd/.nhdr file, a raw file
+// with `sizes`, or baked from a texture at `resolution`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VolumeDef {
+	a: [f64; 3],
+	b: [f64; 3],
+	path: Option<Spanned<String>>,
+	sizes: Option<[usize; 3]>,
+	texture: Option<Spanned<String>>,
+	resolution: Option<[usize; 3]>,
+	// Extinction where the grid is 1
+	density: f64,
+	material: Spanned<String>,
+}
+
+// Color scaled by a density grid, like temperature for emission
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GridTextureDef {
+	a: [f64; 3],
+	b: [f64; 3],
+	path: Option<Spanned<String>>,
+	sizes: Option<[usize; 3]>,
+	texture: Option<Spanned<String>>,
+	resolution: Option<[usize; 3]>,
+	color: [f64; 3],
+}
+
+// Parallelogram from corner q along edges u and v
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuadDef {
+	q: [f64; 3],
+	u: [f64; 3],
+	v: [f64; 3],
+	material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriangleDef {
+	a: [f64; 3],
+	b: [f64; 3],
+	c: [f64; 3],
+	material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiskDef {
+	center: [f64; 3],
+	normal: [f64; 3],
+	radius: f64,
+	material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlaneDef {
+	point: [f64; 3],
+	normal: [f64; 3],
+	material: Spanned<String>,
+}
+
+// Axis aligned box between two opposite corners
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BoxDef {
+	a: [f64; 3],
+	b: [f64; 3],
+	material: Spanned<String>,
+}
+
+// Small meshes written out inline, normals and uvs are per position
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDef {
+	positions: Vec<[f64; 3]>,
+	triangles: Spanned<Vec<[u32; 3]>>,
+	normals: Option<Spanned<Vec<[f64; 3]>>>,
+	uvs: Option<Spanned<Vec<[f64; 2]>>>,
+	// Linear colors for a vertex_color texture
+	colors: Option<Spanned<Vec<[f64; 3]>>>,
+	material: Spanned<String>,
+}
+
+// Mesh file relative to the scene file. OBJ materials come from its .mtl and PLY vertex
+// colors are used, unless a material is given.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshFileDef {
+	path: Spanned<String>,
+	material: Option<Spanned<String>>,
+}
+
+// Meshes, materials and node transforms of a glTF file, its cameras are ignored
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GltfDef {
+	path: Spanned<String>,
+}
+
+// Two shapes combined by `union`, `intersection` or `difference`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CsgDef {
+	op: Spanned<String>,
+	a: Spanned<String>,
+	b: Spanned<String>,
+}
+
+// Distance field parts, blended together when smoothness isn't zero, and optionally
+// repeated count times along each axis
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SdfDef {
+	parts: Spanned<Vec<SdfPartDef>>,
+	#[serde(default)]
+	smoothness: f64,
+	repeat: Option<RepeatDef>,
+	material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case", deny_unknown_fields)]
+enum SdfPartDef {
+	Sphere { center: [f64; 3], radius: f64 },
+	// Boxes go between two opposite corners like the `box` object
+	Box { a: [f64; 3], b: [f64; 3] },
+	RoundedBox { a: [f64; 3], b: [f64; 3], radius: f64 },
+	// Around the y axis
+	Torus { center: [f64; 3], major_radius: f64, minor_radius: f64 },
+	Capsule { a: [f64; 3], b: [f64; 3], radius: f64 },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RepeatDef {
+	spacing: [f64; 3],
+	count: [u32; 3],
+}
+
+#[derive(Deserialize)]
+struct TypeTag {
+	#[serde(rename = "type")]
+	kind: Option<Spanned<String>>,
+}
+
+// Optional on any object, applied as scale, then rotate, then translate. A motion is
+// a list of such transforms at different times.
+#[derive(Deserialize)]
+struct TransformTag {
+	transform: Option<Spanned<TransformDef>>,
+	motion: Option<Spanned<Vec<KeyframeDef>>>,
+}
+
+// Optional on any object, fills it with a constant medium that scatters by its material
+#[derive(Deserialize)]
+struct MediumTag {
+	density: Option<Spanned<f64>>,
+	material: Option<Spanned<String>>,
+}
+
+// Where an object is placed, with the offset of its field in the file
+enum Placement {
+	Transform(TransformDef, usize),
+	Motion(Vec<KeyframeDef>, usize),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransformDef {
+	translate: Option<[f64; 3]>,
+	// Degrees around x, then y, then z
+	rotate: Option<[f64; 3]>,
+	scale: Option<ScaleDef>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDef {
+	Uniform(f64),
+	Axes([f64; 3]),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDef {
+	time: f64,
+	translate: Option<[f64; 3]>,
+	rotate: Option<[f64; 3]>,
+	scale: Option<ScaleDef>,
+}
+
+impl TransformDef {
+	fn matrix(&self) -> Mat4 {
+		keyframe(0.0, self.translate, self.rotate, &self.scale).matrix()
+	}
+}
+
+impl KeyframeDef {
+	fn keyframe(&self) -> Keyframe {
+		keyframe(self.time, self.translate, self.rotate, &self.scale)
+	}
+}
+
+fn keyframe(time: f64, translate: Option<[f64; 3]>, rotate: Option<[f64; 3]>, scale: &Option<ScaleDef>) -> Keyframe {
+	let scale = match scale {
+		Some(ScaleDef::Uniform(s)) => Vec3::new(*s, *s, *s),
+		Some(ScaleDef::Axes(s)) => Vec3::from(*s),
+		None => Vec3::new(1.0, 1.0, 1.0),
+	};
+	let [rx, ry, rz] = rotate.unwrap_or_default();
+	Keyframe::new(time, translate.map_or(Vec3::default(), Vec3::from), Quat::from_euler(rx, ry, rz), scale)
+}
+
+// TOML scene files, or .gltf and .glb files by their extension
+pub fn load_scene<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+	let path = path.as_ref();
+	if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gltf") || ext.eq_ignore_ascii_case("glb")) {
+		return load_gltf(path).map(|scene| scene.into_scene()).map_err(SceneError::Gltf);
+	}
+	let src = fs::read_to_string(path).map_err(|source| SceneError::Io { path: path.to_path_buf(), source })?;
+	parse_scene_in(&src, path.parent().unwrap_or(Path::new("")))
+}
+
+// Files the scene refers to are looked up relative to the working directory
+pub fn parse_scene(src: &str) -> Result<Scene, SceneError> {
+	parse_scene_in(src, Path::new(""))
+}
+
+pub fn parse_scene_in(src: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+	let def: SceneDef = toml::from_str(src).map_err(|e| toml_error(src, 0, e))?;
+	let mut builder = SceneBuilder { src, base_dir, textures: BTreeMap::new(), materials: BTreeMap::new(), shapes: BTreeMap::new() };
+
+	for (name, table) in &def.textures {
+		let tex = builder.texture(&TableSource::new(src, table.span())?)?;
+		builder.textures.insert(name.clone(), tex);
+	}
+	for (name, table) in &def.materials {
+		let mat = builder.material(&TableSource::new(src, table.span())?)?;
+		builder.materials.insert(name.clone(), mat);
+	}
+	// In file order, so a shape can use any shape above it
+	let mut shapes: Vec<_> = def.shapes.iter().collect();
+	shapes.sort_by_key(|(_, table)| table.span().start);
+	for (name, table) in shapes {
+		let shape = builder.placed_object(TableSource::new(src, table.span())?)?;
+		builder.shapes.insert(name.clone(), shape);
+	}
+	let mut world = HittableList::default();
+	for table in &def.objects {
+		world.add(builder.placed_object(TableSource::new(src, table.span())?)?);
+	}
+
+	let mut camera = build_camera(src, &def.camera)?;
+	if let Some(table) = &def.background {
+		camera.background = builder.background(&TableSource::new(src, table.span())?)?;
+	}
+	Ok(Scene { world, camera })
+}
+
+// Turns table definitions into scene objects, keeping everything named so far
+struct SceneBuilder<'a> {
+	src: &'a str,
+	base_dir: &'a Path,
+	textures: BTreeMap<String, Arc<dyn Texture>>,
+	materials: BTreeMap<String, Arc<dyn Material>>,
+	shapes: BTreeMap<String, Arc<dyn Hittable>>,
+}
+
+impl SceneBuilder<'_> {
+	// An object with its transform or motion and medium, if it has them
+	fn placed_object(&self, mut table: TableSource) -> Result<Arc<dyn Hittable>, SceneError> {
+		let src = self.src;
+		let placement = table.take_placement(src)?;
+		let medium = table.take_medium(src)?;
+		let object = self.object(&table)?;
+		let object: Arc<dyn Hittable> = match placement {
+			Some(Placement::Transform(def, offset)) => Arc::new(Instance::try_new(object, def.matrix()).ok_or_else(|| {
+				SceneError::at(src, offset, "transform can't be inverted in field `transform`".to_string())
+			})?),
+			Some(Placement::Motion(defs, offset)) => {
+				let keyframes: Vec<Keyframe> = defs.iter().map(KeyframeDef::keyframe).collect();
+				if keyframes.is_empty() {
+					return Err(SceneError::at(src, offset, "no keyframes in field `motion`".to_string()));
+				}
+				if keyframes.iter().any(|k| k.scale.x() * k.scale.y() * k.scale.z() == 0.0) {
+					return Err(SceneError::at(src, offset, "keyframe scale can't be zero in field `motion`".to_string()));
+				}
+				Arc::new(MovingInstance::new(object, keyframes))
+			}
+			None => object,
+		};
+		// Outside the transform, so density is per world unit
+		Ok(match medium {
+			Some((density, material)) => {
+				let phase_function = self.lookup(&self.materials, &table, &material, "material")?;
+				Arc::new(ConstantMedium::with_phase_function(object, density, phase_function))
+			}
+			None => object,
+		})
+	}
+
+	fn texture(&self, table: &TableSource) -> Result<Arc<dyn Texture>, SceneError> {
+		Ok(match table.kind.as_str() {
+			"solid" => {
+				let d: SolidDef = table.parse(self.src)?;
+				Arc::new(SolidColor::new(d.color.into()))
+			}
+			"checker" => {
+				let d: CheckerDef = table.parse(self.src)?;
+				Arc::new(CheckerTexture::from_colors(d.scale, d.even.into(), d.odd.into()))
+			}
+			"image" => {
+				let d: ImageDef = table.parse(self.src)?;
+				let path = self.base_dir.join(d.path.get_ref());
+				let tex = ImageTexture::load(&path).map_err(|e| {
+					SceneError::at(self.src, table.offset + d.path.span().start, format!("cannot load {}: {e}", path.display()))
+				})?;
+				Arc::new(tex)
+			}
+			"noise" => {
+				let d: NoiseDef = table.parse(self.src)?;
+				let pattern = match d.pattern.get_ref().as_str() {
+					"noise" => NoisePattern::Noise,
+					"turbulence" => NoisePattern::Turbulence,
+					"fbm" => NoisePattern::Fbm,
+					"marble" => NoisePattern::Marble,
+					"wood" => NoisePattern::Wood,
+					other => return Err(SceneError::at(self.src, table.offset + d.pattern.span().start, format!(
+						"unknown pattern `{other}` in field `pattern`, expected one of `noise`, `turbulence`, `fbm`, `marble`, `wood`"
+					))),
+				};
+				let mut tex = NoiseTexture::new(d.seed, pattern, d.frequency);
+				if let Some(octaves) = d.octaves { tex.octaves = octaves; }
+				if let Some(lacunarity) = d.lacunarity { tex.lacunarity = lacunarity; }
+				if let Some(gain) = d.gain { tex.gain = gain; }
+				if let Some(low) = d.low { tex.low = low.into(); }
+				if let Some(high) = d.high { tex.high = high.into(); }
+				Arc::new(tex)
+			}
+			"vertex_color" => {
+				let d: VertexColorDef = table.parse(self.src)?;
+				Arc::new(VertexColorTexture::new(d.fallback.map_or(Color::new(0.8, 0.8, 0.8), Color::from)))
+			}
+			"grid" => {
+				let d: GridTextureDef = table.parse(self.src)?;
+				let bbox = Aabb::from_points(d.a.into(), d.b.into());
+				let grid = self.grid(table, &bbox, d.path.as_ref(), d.sizes, d.texture.as_ref(), d.resolution)?;
+				Arc::new(GridTexture::new(grid, bbox, d.color.into()))
+			}
+			_ => return Err(table.unknown_type(self.src, &["solid", "checker", "image", "noise", "vertex_color", "grid"])),
+		})
+	}
+
+	fn material(&self, table: &TableSource) -> Result<Arc<dyn Material>, SceneError> {
+		Ok(match table.kind.as_str() {
+			"lambertian" => {
+				let d: LambertianDef = table.parse(self.src)?;
+				Arc::new(Lambertian::from_texture(self.color_or_texture(table, "albedo", d.albedo, d.texture.as_ref())?))
+			}
+			"metal" => {
+				let d: MetalDef = table.parse(self.src)?;
+				Arc::new(Metal::from_texture(self.color_or_texture(table, "albedo", d.albedo, d.texture.as_ref())?, d.fuzz))
+			}
+			"dielectric" => {
+				let d: DielectricDef = table.parse(self.src)?;
+				Arc::new(Dielectric { refraction_index: d.refraction_index })
+			}
+			"diffuse_light" => {
+				let d: DiffuseLightDef = table.parse(self.src)?;
+				Arc::new(DiffuseLight::from_texture(self.color_or_texture(table, "emit", d.emit, d.texture.as_ref())?))
+			}
+			// Same fields as lambertian, for objects given a density
+			"isotropic" => {
+				let d: LambertianDef = table.parse(self.src)?;
+				Arc::new(Isotropic::from_texture(self.color_or_texture(table, "albedo", d.albedo, d.texture.as_ref())?))
+			}
+			"henyey_greenstein" => {
+				let d: HenyeyGreensteinDef = table.parse(self.src)?;
+				let albedo = self.color_or_texture(table, "albedo", d.albedo, d.texture.as_ref())?;
+				let emission = match (d.emission, &d.emission_texture) {
+					(None, None) => Arc::new(SolidColor::new(Color::default())),
+					(emission, texture) => self.color_or_texture(table, "emission", emission, texture.as_ref())?,
+				};
+				Arc::new(HenyeyGreenstein::from_textures(albedo, d.g, emission))
+			}
+			_ => return Err(table.unknown_type(self.src, &["lambertian", "metal", "dielectric", "diffuse_light", "isotropic", "henyey_greenstein"])),
+		})
+	}
+
+	fn object(&self, table: &TableSource) -> Result<Arc<dyn Hittable>, SceneError> {
+		Ok(match table.kind.as_str() {
+			"sphere" => {
+				let d: SphereDef = table.parse(self.src)?;
+				let mat = self.lookup(&self.materials, table, &d.material, "material")?;
+				match d.center2 {
+					Some(center2) => Arc::new(Sphere::moving(d.center.into(), center2.into(), d.radius, mat)),
+					None => Arc::new(Sphere::stationary(d.center.into(), d.radius, mat)),
+				}
+			}
+			"quad" => {
+				let d: QuadDef = table.parse(self.src)?;
+				let mat = self.lookup(&self.materials, table, &d.material, "material")?;
+				Arc::new(Quad::new(d.q.into(), d.u.into(), d.v.into(), mat))
+			}
+			"triangle" => {
+				let d: TriangleDef = table.parse(self.src)?;
+				let mat = self.lookup(&self.materials, table, &d.material, "material")?;
+				Arc::new(Triangle::new(d.a.into(), d.b.into(), d.c.into(), mat))
+			}
+			"disk" => {
+				let d: DiskDef = table.parse(self.src)?;
+				let mat = self.lookup(&self.materials, table, &d.material, "material")?;
+				Arc::new(Disk::new(d.center.into(), d.normal.into(), d.radius, mat))
+			}
+			"plane" => {
+				let d: PlaneDef = table.parse(self.src)?;
+				let mat = self.lookup(&self.materials, table, &d.material, "material")?;
+				Arc::new(Plane::new(d.point.into(), d.normal.into(), mat))
+			}
+			"box" => {
+				let d: BoxDef = table.parse(self.src)?;
+				let mat = self.lookup(&self.materials, table, &d.material, "material")?;
+				Arc::new(make_box(d.a.into(), d.b.into(), mat))
+			}
+			"mesh" => {
+				let d: MeshDef = table.parse(self.src)?;
+				let mat = self.lookup(&self.materials, table, &d.material, "material")?;
+				let vertex_count = d.positions.len();
+				let normals = d.normals.as_ref().map(|n| (n.span(), n.get_ref().len()));
+				let uvs = d.uvs.as_ref().map(|uv| (uv.span(), uv.get_ref().len()));
+				let colors = d.colors.as_ref().map(|c| (c.span(), c.get_ref().len()));
+				for (field, values) in [("normals", normals), ("uvs", uvs), ("colors", colors)] {
+					if let Some((span, len)) = values.filter(|&(_, len)| len != vertex_count) {
+						return Err(SceneError::at(self.src, table.offset + span.start, format!("{len} {field} for {vertex_count} positions in field `{field}`")));
+					}
+				}
+				if let Some(index) = d.triangles.get_ref().iter().flatten().find(|&&i| i as usize >= vertex_count) {
+					return Err(SceneError::at(self.src, table.offset + d.triangles.span().start, format!("vertex {index} out of range for {vertex_count} positions in field `triangles`")));
+				}
+				let data = MeshData {
+					positions: d.positions.into_iter().map(Point3::from).collect(),
+					normals: d.normals.map_or_else(Vec::new, |n| n.into_inner().into_iter().map(Vec3::from).collect()),
+					uvs: d.uvs.map_or_else(Vec::new, |uv| uv.into_inner().into_iter().map(|[u, v]| (u, v)).collect()),
+					colors: d.colors.map_or_else(Vec::new, |c| c.into_inner().into_iter().map(Color::from).collect()),
+				};
+				Arc::new(TriangleMesh::new(Arc::new(data), d.triangles.into_inner(), mat))
+			}
+			"obj" => {
+				let d: MeshFileDef = table.parse(self.src)?;
+				let path = self.base_dir.join(d.path.get_ref());
+				// The error already names the file
+				let mut model = load_obj(&path).map_err(|e| SceneError::at(self.src, table.offset + d.path.span().start, e.to_string()))?;
+				if let Some(name) = &d.material {
+					let mat = self.lookup(&self.materials, table, name, "material")?;
+					for group in &mut model.groups {
+						group.material = mat.clone();
+					}
+				}
+				Arc::new(model.into_hittables())
+			}
+			"ply" | "stl" => {
+				let d: MeshFileDef = table.parse(self.src)?;
+				let path = self.base_dir.join(d.path.get_ref());
+				let loaded = if table.kind == "ply" {
+					load_ply(&path).map_err(|e| e.to_string())
+				} else {
+					load_stl(&path).map_err(|e| e.to_string())
+				};
+				let loaded = loaded.map_err(|e| SceneError::at(self.src, table.offset + d.path.span().start, e))?;
+				let mat = match &d.material {
+					Some(name) => self.lookup(&self.materials, table, name, "material")?,
+					None => loaded.default_material(),
+				};
+				Arc::new(loaded.into_mesh(mat))
+			}
+			"gltf" => {
+				let d: GltfDef = table.parse(self.src)?;
+				let path = self.base_dir.join(d.path.get_ref());
+				let scene = load_gltf(&path).map_err(|e| SceneError::at(self.src, table.offset + d.path.span().start, e.to_string()))?;
+				Arc::new(scene.world)
+			}
+			"volume" => {
+				let d: VolumeDef = table.parse(self.src)?;
+				let bbox = Aabb::from_points(d.a.into(), d.b.into());
+				let grid = self.grid(table, &bbox, d.path.as_ref(), d.sizes, d.texture.as_ref(), d.resolution)?;
+				let mat = self.lookup(&self.materials, table, &d.material, "material")?;
+				Arc::new(GridVolume::new(grid, bbox, d.density, mat))
+			}
+			"csg" => {
+				let d: CsgDef = table.parse(self.src)?;
+				let op = match d.op.get_ref().as_str() {
+					"union" => CsgOp::Union,
+					"intersection" => CsgOp::Intersection,
+					"difference" => CsgOp::Difference,
+					other => return Err(SceneError::at(self.src, table.offset + d.op.span().start, format!(
+						"unknown op `{other}` in field `op`, expected one of `union`, `intersection`, `difference`"
+					))),
+				};
+				let shape = |name: &Spanned<String>, field: &str| self.shapes.get(name.get_ref()).cloned().ok_or_else(|| {
+					SceneError::at(self.src, table.offset + name.span().start, format!("unknown shape `{}` in field `{field}`", name.get_ref()))
+				});
+				Arc::new(Csg::new(op, shape(&d.a, "a")?, shape(&d.b, "b")?))
+			}
+			"sdf" => {
+				let d: SdfDef = table.parse(self.src)?;
+				let mat = self.lookup(&self.materials, table, &d.material, "material")?;
+				let span = d.parts.span();
+				let parts = d.parts.into_inner().into_iter().map(|part| -> Arc<dyn Sdf> {
+					let corners = |a: [f64; 3], b: [f64; 3]| {
+						let (a, b) = (Point3::from(a), Point3::from(b));
+						let half = b - a;
+						(0.5 * (a + b), Vec3::new(half.x().abs(), half.y().abs(), half.z().abs()) / 2.0)
+					};
+					match part {
+						SdfPartDef::Sphere { center, radius } => Arc::new(SphereSdf::new(center.into(), radius)),
+						SdfPartDef::Box { a, b } => {
+							let (center, half_size) = corners(a, b);
+							Arc::new(BoxSdf::new(center, half_size))
+						}
+						SdfPartDef::RoundedBox { a, b, radius } => {
+							let (center, half_size) = corners(a, b);
+							Arc::new(RoundedBoxSdf::new(center, half_size, radius))
+						}
+						SdfPartDef::Torus { center, major_radius, minor_radius } => Arc::new(TorusSdf::new(center.into(), major_radius, minor_radius)),
+						SdfPartDef::Capsule { a, b, radius } => Arc::new(CapsuleSdf::new(a.into(), b.into(), radius)),
+					}
+				});
+				let Some(mut sdf) = parts.reduce(|a, b| Arc::new(SmoothUnion::new(a, b, d.smoothness))) else {
+					return Err(SceneError::at(self.src, table.offset + span.start, "no parts in field `parts`".to_string()));
+				};
+				if let Some(repeat) = d.repeat {
+					sdf = Arc::new(Repeat::new(sdf, repeat.spacing.into(), repeat.count));
+				}
+				Arc::new(SdfHittable::new(sdf, mat))
+			}
+			_ => return Err(table.unknown_type(self.src, &["sphere", "quad", "triangle", "disk", "plane", "box", "mesh", "obj", "ply", "stl", "gltf", "volume", "csg", "sdf"])),
+		})
+	}
+
+	// A grid from exactly one of a file or a texture to bake
+	fn grid(&self, table: &TableSource, bbox: &Aabb, path: Option<&Spanned<String>>, sizes: Option<[usize; 3]>, texture: Option<&Spanned<String>>, resolution: Option<[usize; 3]>) -> Result<Arc<DensityGrid>, SceneError> {
+		let grid = match (path, texture) {
+			(Some(path), None) => {
+				let file = self.base_dir.join(path.get_ref());
+				let is_nrrd = file.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("nrrd") || ext.eq_ignore_ascii_case("nhdr"));
+				let loaded = match (is_nrrd, sizes) {
+					(true, _) => DensityGrid::load_nrrd(&file),
+					(false, Some(sizes)) => DensityGrid::load_raw(&file, sizes),
+					(false, None) => return Err(SceneError::at(self.src, table.header_offset, "missing field `sizes` for a raw grid".to_string())),
+				};
+				loaded.map_err(|e| SceneError::at(self.src, table.offset + path.span().start, e.to_string()))?
+			}
+			(None, Some(name)) => {
+				let tex = self.lookup(&self.textures, table, name, "texture")?;
+				DensityGrid::from_texture(resolution.unwrap_or([64, 64, 64]), tex.as_ref(), bbox)
+			}
+			(Some(_), Some(name)) => return Err(SceneError::at(self.src, table.offset + name.span().start, "only one of `path` and `texture` can be given".to_string())),
+			(None, None) => return Err(SceneError::at(self.src, table.header_offset, "missing field `path` or `texture`".to_string())),
+		};
+		if grid.sizes.contains(&0) {
+			return Err(SceneError::at(self.src, table.header_offset, "grid has no voxels".to_string()));
+		}
+		Ok(Arc::new(grid))
+	}
+
+	fn background(&self, table: &TableSource) -> Result<Background, SceneError> {
+		Ok(match table.kind.as_str() {
+			"gradient" => {
+				let d: GradientDef = table.parse(self.src)?;
+				Background::Gradient { bottom: d.bottom.into(), top: d.top.into() }
+			}
+			"solid" => {
+				let d: SolidDef = table.parse(self.src)?;
+				Background::Solid(d.color.into())
+			}
+			"environment" => {
+				let d: EnvironmentDef = table.parse(self.src)?;
+				Background::Environment(self.lookup(&self.textures, table, &d.texture, "texture")?)
+			}
+			_ => return Err(table.unknown_type(self.src, &["gradient", "solid", "environment"])),
+		})
+	}
+
+	// `field` holds a plain color, `texture` the name of a texture
+	fn color_or_texture(&self, table: &TableSource, field: &str, color: Option<[f64; 3]>, texture: Option<&Spanned<String>>) -> Result<Arc<dyn Texture>, SceneError> {
+		match (color, texture) {
+			(Some(color), None) => Ok(Arc::new(SolidColor::new(Color::from(color)))),
+			(None, Some(name)) => self.lookup(&self.textures, table, name, "texture"),
+			(Some(_), Some(name)) => Err(SceneError::at(self.src, table.offset + name.span().start, format!("only one of `{field}` and `texture` can be given"))),
+			(None, None) => Err(SceneError::at(self.src, table.header_offset, format!("missing field `{field}` or `texture`"))),
+		}
+	}
+
+	// Reports a bad reference on the line that names it
+	fn lookup<T: ?Sized>(&self, items: &BTreeMap<String, Arc<T>>, table: &TableSource, name: &Spanned<String>, field: &str) -> Result<Arc<T>, SceneError> {
+		items.get(name.get_ref()).cloned().ok_or_else(|| {
+			SceneError::at(self.src, table.offset + name.span().start, format!("unknown {field} `{}` in field `{field}`", name.get_ref()))
+		})
+	}
+}
+
+// The body of one `[materials.name]`, `[[objects]]` or similar typed table
+struct TableSource {
+	// Body with the `type` line blanked out so the typed structs can deny unknown fields
+	body: String,
+	// Where the header and body start in the whole file
+	header_offset: usize,
+	offset: usize,
+	kind: String,
+	kind_offset: usize,
+}
+
+impl TableSource {
+	fn new(src: &str, span: Range<usize>) -> Result<Self, SceneError> {
+		let header_len = src[span.clone()].find('\n').map_or(span.len(), |i| i + 1);
+		let offset = span.start + header_len;
+		let mut body = src[offset..span.end].to_string();
+
+		let tag: TypeTag = toml::from_str(&body).map_err(|e| toml_error(src, offset, e))?;
+		let Some(kind) = tag.kind else {
+			return Err(SceneError::at(src, span.start, "missing field `type`".to_string()));
+		};
+		let kind_span = kind.span();
+		let line_start = body[..kind_span.start].rfind('\n').map_or(0, |i| i + 1);
+		let line_end = body[kind_span.end..].find('\n').map_or(body.len(), |i| kind_span.end + i);
+		blank(&mut body, line_start..line_end);
+
+		Ok(TableSource { body, header_offset: span.start, offset, kind: kind.into_inner(), kind_offset: offset + kind_span.start })
+	}
+
+	// Removes an object's `transform` or `motion` like the type
+	fn take_placement(&mut self, src: &str) -> Result<Option<Placement>, SceneError> {
+		let tag: TransformTag = toml::from_str(&self.body).map_err(|e| toml_error(src, self.offset, e))?;
+		let span = match (&tag.transform, &tag.motion) {
+			(Some(_), Some(motion)) => {
+				return Err(SceneError::at(src, self.offset + motion.span().start, "only one of `transform` and `motion` can be given".to_string()));
+			}
+			(Some(transform), None) => transform.span(),
+			(None, Some(motion)) => motion.span(),
+			(None, None) => return Ok(None),
+		};
+		// Motions can span several lines, blanking keeps the offsets of everything after
+		let line_start = self.body[..span.start].rfind('\n').map_or(0, |i| i + 1);
+		blank(&mut self.body, line_start..span.end);
+		let offset = self.offset + line_start;
+		Ok(match (tag.transform, tag.motion) {
+			(Some(transform), _) => Some(Placement::Transform(transform.into_inner(), offset)),
+			(_, motion) => motion.map(|motion| Placement::Motion(motion.into_inner(), offset)),
+		})
+	}
+
+	// Removes an object's `density`, with the material it scatters by
+	fn take_medium(&mut self, src: &str) -> Result<Option<(f64, Spanned<String>)>, SceneError> {
+		// Volumes have a density of their own
+		if self.kind == "volume" {
+			return Ok(None);
+		}
+		let tag: MediumTag = toml::from_str(&self.body).map_err(|e| toml_error(src, self.offset, e))?;
+		let Some(density) = tag.density else {
+			return Ok(None);
+		};
+		let span = density.span();
+		if *density.get_ref() <= 0.0 {
+			return Err(SceneError::at(src, self.offset + span.start, "density has to be positive in field `density`".to_string()));
+		}
+		let Some(material) = tag.material else {
+			return Err(SceneError::at(src, self.offset + span.start, "a medium needs a `material` to scatter by".to_string()));
+		};
+		let line_start = self.body[..span.start].rfind('\n').map_or(0, |i| i + 1);
+		blank(&mut self.body, line_start..span.end);
+		Ok(Some((density.into_inner(), material)))
+	}
+
+	fn parse<T: DeserializeOwned>(&self, src: &str) -> Result<T, SceneError> {
+		toml::from_str(&self.body).map_err(|e| {
+			// Errors about the table as a whole, like a missing field, go on its header
+			if e.span().is_some_and(|span| span.start == 0 && span.end >= self.body.trim_end().len()) {
+				SceneError::at(src, self.header_offset, e.message().trim().to_string())
+			} else {
+				toml_error(src, self.offset, e)
+			}
+		})
+	}
+
+	fn unknown_type(&self, src: &str, expected: &[&str]) -> SceneError {
+		let expected: Vec<String> = expected.iter().map(|kind| format!("`{kind}`")).collect();
+		SceneError::at(src, self.kind_offset, format!("unknown type `{}` in field `type`, expected one of {}", self.kind, expected.join(", ")))
+	}
+}
+
+// Spaces keep every later offset pointing at the same place in the file
+fn blank(body: &mut String, range: Range<usize>) {
+	let len = range.len();
+	body.replace_range(range, &" ".repeat(len));
+}
+
+fn toml_error(src: &str, offset: usize, e: toml::de::Error) -> SceneError {
+	let start = e.span().map_or(0, |span| span.start);
+	let mut err = SceneError::at(src, offset + start, e.message().trim().to_string());
+	// Type errors don't name the field, take it from the offending line
+	if let SceneError::Parse { message, context, .. } = &mut err {
+		if let Some((key, _)) = context.split_once('=') {
+			// Not for lines inside an array of inline tables
+			let key = key.trim();
+			if !message.contains("field") && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+				message.push_str(&format!(" in field `{key}`"));
+			}
+		}
+	}
+	err
+}
+
+fn build_camera(src: &str, def: &CameraDef) -> Result<Camera, SceneError> {
+	let mut cam = Camera::new(def.aspect_ratio.unwrap_or(16.0 / 9.0), def.image_width.unwrap_or(400));
+	if let Some(samples_per_pixel) = def.samples_per_pixel { cam.samples_per_pixel = samples_per_pixel; }
+	if let Some(max_depth) = def.max_depth { cam.max_depth = max_depth; }
+	if let Some(fov) = def.fov { cam.fov = fov; }
+	if let Some(look_from) = def.look_from { cam.look_from = look_from.into(); }
+	if let Some(look_at) = def.look_at { cam.look_at = look_at.into(); }
+	if let Some(vup) = def.vup { cam.vup = vup.into(); }
+	if let Some(defocus_angle) = def.defocus_angle { cam.defocus_angle = defocus_angle; }
+	if let Some(focus_dist) = def.focus_dist { cam.focus_dist = focus_dist; }
+	if let Some(shutter_open) = &def.shutter_open { cam.shutter_open = *shutter_open.get_ref(); }
+	if let Some(shutter_close) = &def.shutter_close { cam.shutter_close = *shutter_close.get_ref(); }
+	// Checked once both ends are known, so an open time past the default close is caught too
+	if cam.shutter_close < cam.shutter_open {
+		let (field, span) = match &def.shutter_close {
+			Some(close) => ("shutter_close", close.span()),
+			None => ("shutter_open", def.shutter_open.as_ref().map_or(0..0, |open| open.span())),
+		};
+		return Err(SceneError::at(src, span.start, format!("shutter has to close after it opens in field `{field}`")));
+	}
+	if let Some(curve) = &def.shutter_curve {
+		let err = |message: &str| SceneError::at(src, curve.span().start, format!("{message} in field `shutter_curve`"));
+		cam.shutter_curve = match curve.get_ref() {
+			ShutterCurveDef::Named(name) if name == "box" => ShutterCurve::Box,
+			ShutterCurveDef::Named(name) if name == "triangle" => ShutterCurve::Triangle,
+			ShutterCurveDef::Named(name) => return Err(err(&format!("unknown curve `{name}`, expected `box`, `triangle` or a list of weights"))),
+			ShutterCurveDef::Weights(weights) if weights.len() < 2 => return Err(err("at least two weights are needed")),
+			ShutterCurveDef::Weights(weights) if weights.iter().any(|w| *w < 0.0) || weights.iter().all(|w| *w == 0.0) => {
+				return Err(err("weights can't be negative or all zero"));
+			}
+			ShutterCurveDef::Weights(weights) => ShutterCurve::custom(weights),
+		};
+	}
+	Ok(cam)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{hittable::HitRecord, interval::Interval, ray::Ray};
+
+	// Line and message of a parse error
+	fn parse_error(src: &str) -> (usize, String) {
+		match parse_scene(src).err().unwrap() {
+			SceneError::Parse { line, message, .. } => (line, message),
+			e => panic!("expected a parse error, got {e}"),
+		}
+	}
+
+	const GLASS: &str = "[materials.glass]\ntype = \"dielectric\"\nrefraction_index = 1.5\n";
+
+	#[test]
+	fn unknown_material() {
+		let (line, message) = parse_error(&format!("{GLASS}\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"glas\"\n"));
+		assert_eq!((line, message.as_str()), (9, "unknown material `glas` in field `material`"));
+	}
+
+	#[test]
+	fn wrong_field_type() {
+		let (line, message) = parse_error(&format!("{GLASS}\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = \"big\"\nmaterial = \"glass\"\n"));
+		assert_eq!(line, 8);
+		assert!(message.starts_with("invalid type: string \"big\"") && message.ends_with(" in field `radius`"), "{message}");
+
+		// Fields of the camera are checked while reading the whole file
+		let (line, message) = parse_error("[camera]\nfov = 40\nimage_width = 1.5\n");
+		assert_eq!(line, 3);
+		assert!(message.ends_with(" in field `image_width`"), "{message}");
+
+		let (line, message) = parse_error("[materials.glass]\ntype = \"dielectric\"\nrefraction_index = [1.5]\n");
+		assert_eq!(line, 3);
+		assert!(message.ends_with(" in field `refraction_index`"), "{message}");
+	}
+
+	#[test]
+	fn unknown_type() {
+		let (line, message) = parse_error(&format!("{GLASS}\n[[objects]]\ntype = \"cube\"\nmaterial = \"glass\"\n"));
+		assert_eq!(line, 6);
+		assert!(message.starts_with("unknown type `cube` in field `type`, expected one of `sphere`, "), "{message}");
+
+		let (line, message) = parse_error("[materials.glass]\ntype = \"glas\"\n");
+		assert_eq!(line, 2);
+		assert!(message.starts_with("unknown type `glas` in field `type`"), "{message}");
+	}
+
+	#[test]
+	fn unknown_field() {
+		let (line, message) = parse_error(&format!("{GLASS}\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nradiuss = 2\nmaterial = \"glass\"\n"));
+		assert_eq!(line, 9);
+		assert!(message.starts_with("unknown field `radiuss`"), "{message}");
+	}
+
+	#[test]
+	fn shutter_order() {
+		let (line, message) = parse_error("[camera]\nshutter_open = 0.5\nshutter_close = 0.25\n");
+		assert_eq!((line, message.as_str()), (3, "shutter has to close after it opens in field `shutter_close`"));
+		// Opening after the default close of 1
+		let (line, message) = parse_error("[camera]\nfov = 40\nshutter_open = 2.0\n");
+		assert_eq!((line, message.as_str()), (3, "shutter has to close after it opens in field `shutter_open`"));
+
+		let cam = parse_scene("[camera]\nshutter_open = 1.0\nshutter_close = 2.0\n").ok().unwrap().camera;
+		assert_eq!((cam.shutter_open, cam.shutter_close), (1.0, 2.0));
+	}
+
+	// The second frame of a sequence, moving from x = 0 to x = 2 while the shutter is open
+	#[test]
+	fn motion_over_later_shutter() {
+		let scene = parse_scene(r#"
+[camera]
+shutter_open = 1.0
+shutter_close = 2.0
+
+[materials.glass]
+type = "dielectric"
+refraction_index = 1.5
+
+[[objects]]
+type = "sphere"
+center = [0, 0, 0]
+radius = 0.5
+material = "glass"
+motion = [{ time = 1, translate = [0, 0, 0] }, { time = 2, translate = [2, 0, 0] }]
+"#).unwrap_or_else(|e| panic!("{e}"));
+		let hit_at = |x: f64, time: f64| {
+			let r = Ray::new(Point3::new(x, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), time);
+			scene.world.hit(&r, Interval::new(0.001, f64::INFINITY), &mut HitRecord::default())
+		};
+		assert!(hit_at(1.0, 1.5) && !hit_at(0.0, 1.5));
+		assert!(hit_at(2.0, 2.0) && !hit_at(1.0, 2.0));
+		assert!(scene.world.bounding_box().x.contains(2.4));
+	}
+}
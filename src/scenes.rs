@@ -0,0 +1,317 @@
+use std::{io, path::Path, sync::Arc};
+
+use crate::{bvh::BvhNode, camera::{Background, Camera}, color::Color, constant_medium::ConstantMedium, hittable_list::HittableList, instance::Instance, mat4::Mat4, material::{Dielectric, DiffuseLight, Lambertian, Metal}, quad::{make_box, Quad}, rng::{random_double, random_range}, scene::Scene, sphere::Sphere, texture::{CheckerTexture, ImageTexture, NoisePattern, NoiseTexture}, vec3::{Point3, Vec3}};
+
+// Only fails for scenes that read files
+pub type SceneBuilder = fn() -> io::Result<Scene>;
+
+// Texture of the globes in earth and final_scene, relative to the working directory
+pub const EARTH_MAP: &str = "images/earthmap.jpg";
+
+// Scenes from the book series, by the name the binary knows them as
+pub const BUILTIN: &[(&str, SceneBuilder)] = &[
+	("random_spheres", || Ok(random_spheres())),
+	("checkered_spheres", || Ok(checkered_spheres())),
+	("earth", || earth(EARTH_MAP)),
+	("perlin_spheres", || Ok(perlin_spheres())),
+	("quads", || Ok(quads())),
+	("simple_light", || Ok(simple_light())),
+	("cornell_box", || Ok(cornell_box())),
+	("cornell_smoke", || Ok(cornell_smoke())),
+	("final_scene", || final_scene(EARTH_MAP)),
+];
+
+pub fn by_name(name: &str) -> Option<io::Result<Scene>> {
+	BUILTIN.iter().find(|(n, _)| *n == name).map(|(_, build)| build())
+}
+
+// Names the file in the error, which the image reader doesn't
+fn load_earth_map<P: AsRef<Path>>(path: P) -> io::Result<ImageTexture> {
+	let path = path.as_ref();
+	ImageTexture::load(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))
+}
+
+// The cover of "Ray Tracing in One Weekend", with bouncing diffuse spheres
+pub fn random_spheres() -> Scene {
+	let mut world = HittableList::default();
+
+	let ground_mat = Lambertian::new(Color::new(0.5,0.5,0.5));
+	world.add(Arc::new(Sphere::stationary(Point3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(ground_mat))));
+
+	for a in -11..11 {
+		for b in -11..11 {
+			let choose_mat = random_double();
+			let center = Point3::new(a as f64 + 0.9*random_double(), 0.2, b as f64+ 0.9*random_double());
+
+			if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+				if choose_mat < 0.8 {
+					let albedo = Color::random() * Color::random();
+					let sphere_material = Lambertian::new(albedo);
+					let center2 = center + Vec3::new(0.0, random_range(0.0..0.5), 0.0);
+					world.add(Arc::new(Sphere::moving(center, center2, 0.2, Arc::new(sphere_material))));
+				} else if choose_mat < 0.95 {
+					let albedo = Color::random() * Color::random();
+					let fuzz = random_range(0.0..0.5);
+					let sphere_material = Metal::new(albedo, fuzz);
+					world.add(Arc::new(Sphere::stationary(center, 0.2, Arc::new(sphere_material))));
+				} else {
+					let sphere_material = Dielectric { refraction_index: 1.5};
+					world.add(Arc::new(Sphere::stationary(center, 0.2, Arc::new(sphere_material))));
+				}
+			}
+		}
+	}
+
+	let material_one = Dielectric { refraction_index: 1.5};
+	world.add(Arc::new(Sphere::stationary(Point3::new(0.0, 1.0, 0.0), 1.0, Arc::new(material_one))));
+	let material_two = Lambertian::new(Color::new(0.4, 0.2, 0.1));
+	world.add(Arc::new(Sphere::stationary(Point3::new(-4.0, 1.0, 0.0), 1.0, Arc::new(material_two))));
+	let material_three = Metal::new(Color::new(0.7, 0.6, 0.5), 0.0);
+	world.add(Arc::new(Sphere::stationary(Point3::new(4.0, 1.0, 0.0), 1.0, Arc::new(material_three))));
+
+	let mut cam = Camera::new(16.0/9.0, 300);
+	cam.samples_per_pixel = 40;
+	cam.max_depth = 10;
+	cam.look_from = Point3::new(13.0, 2.0, 3.0);
+	cam.look_at = Point3::new(0.0, 0.0, 0.0);
+	cam.fov = 20.0;
+	cam.defocus_angle = 0.6;
+	cam.focus_dist = 10.0;
+
+	Scene { world, camera: cam }
+}
+
+pub fn checkered_spheres() -> Scene {
+	let mut world = HittableList::default();
+
+	let checker = Arc::new(CheckerTexture::from_colors(0.32, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9)));
+	world.add(Arc::new(Sphere::stationary(Point3::new(0.0, -10.0, 0.0), 10.0, Arc::new(Lambertian::from_texture(checker.clone())))));
+	world.add(Arc::new(Sphere::stationary(Point3::new(0.0, 10.0, 0.0), 10.0, Arc::new(Lambertian::from_texture(checker)))));
+
+	let mut cam = Camera::new(16.0/9.0, 400);
+	cam.samples_per_pixel = 100;
+	cam.max_depth = 50;
+	cam.fov = 20.0;
+	cam.look_from = Point3::new(13.0, 2.0, 3.0);
+	cam.look_at = Point3::new(0.0, 0.0, 0.0);
+	cam.defocus_angle = 0.0;
+
+	Scene { world, camera: cam }
+}
+
+// A globe textured with the lat-long map at earth_map
+pub fn earth<P: AsRef<Path>>(earth_map: P) -> io::Result<Scene> {
+	let earth_texture = load_earth_map(earth_map)?;
+	let earth_surface = Arc::new(Lambertian::from_texture(Arc::new(earth_texture)));
+	let mut world = HittableList::default();
+	world.add(Arc::new(Sphere::stationary(Point3::new(0.0, 0.0, 0.0), 2.0, earth_surface)));
+
+	let mut cam = Camera::new(16.0/9.0, 400);
+	cam.samples_per_pixel = 100;
+	cam.max_depth = 50;
+	cam.fov = 20.0;
+	cam.look_from = Point3::new(0.0, 0.0, 12.0);
+	cam.look_at = Point3::new(0.0, 0.0, 0.0);
+	cam.defocus_angle = 0.0;
+
+	Ok(Scene { world, camera: cam })
+}
+
+pub fn perlin_spheres() -> Scene {
+	let mut world = HittableList::default();
+
+	let pertext = Arc::new(NoiseTexture::new(0, NoisePattern::Marble, 4.0));
+	world.add(Arc::new(Sphere::stationary(Point3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(Lambertian::from_texture(pertext.clone())))));
+	world.add(Arc::new(Sphere::stationary(Point3::new(0.0, 2.0, 0.0), 2.0, Arc::new(Lambertian::from_texture(pertext)))));
+
+	let mut cam = Camera::new(16.0/9.0, 400);
+	cam.samples_per_pixel = 100;
+	cam.max_depth = 50;
+	cam.fov = 20.0;
+	cam.look_from = Point3::new(13.0, 2.0, 3.0);
+	cam.look_at = Point3::new(0.0, 0.0, 0.0);
+	cam.defocus_angle = 0.0;
+
+	Scene { world, camera: cam }
+}
+
+
+// Five colored quads around the camera axis
+pub fn quads() -> Scene {
+	let mut world = HittableList::default();
+
+	let left_red = Arc::new(Lambertian::new(Color::new(1.0, 0.2, 0.2)));
+	let back_green = Arc::new(Lambertian::new(Color::new(0.2, 1.0, 0.2)));
+	let right_blue = Arc::new(Lambertian::new(Color::new(0.2, 0.2, 1.0)));
+	let upper_orange = Arc::new(Lambertian::new(Color::new(1.0, 0.5, 0.0)));
+	let lower_teal = Arc::new(Lambertian::new(Color::new(0.2, 0.8, 0.8)));
+
+	world.add(Arc::new(Quad::new(Point3::new(-3.0, -2.0, 5.0), Vec3::new(0.0, 0.0, -4.0), Vec3::new(0.0, 4.0, 0.0), left_red)));
+	world.add(Arc::new(Quad::new(Point3::new(-2.0, -2.0, 0.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 0.0), back_green)));
+	world.add(Arc::new(Quad::new(Point3::new(3.0, -2.0, 1.0), Vec3::new(0.0, 0.0, 4.0), Vec3::new(0.0, 4.0, 0.0), right_blue)));
+	world.add(Arc::new(Quad::new(Point3::new(-2.0, 3.0, 1.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 4.0), upper_orange)));
+	world.add(Arc::new(Quad::new(Point3::new(-2.0, -3.0, 5.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -4.0), lower_teal)));
+
+	let mut cam = Camera::new(1.0, 400);
+	cam.samples_per_pixel = 100;
+	cam.max_depth = 50;
+	cam.fov = 80.0;
+	cam.look_from = Point3::new(0.0, 0.0, 9.0);
+	cam.look_at = Point3::new(0.0, 0.0, 0.0);
+	cam.defocus_angle = 0.0;
+
+	Scene { world, camera: cam }
+}
+
+// Marble spheres lit only by an emissive sphere and quad
+pub fn simple_light() -> Scene {
+	let mut world = HittableList::default();
+
+	let pertext = Arc::new(NoiseTexture::new(0, NoisePattern::Marble, 4.0));
+	world.add(Arc::new(Sphere::stationary(Point3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(Lambertian::from_texture(pertext.clone())))));
+	world.add(Arc::new(Sphere::stationary(Point3::new(0.0, 2.0, 0.0), 2.0, Arc::new(Lambertian::from_texture(pertext)))));
+
+	let difflight = Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
+	world.add(Arc::new(Sphere::stationary(Point3::new(0.0, 7.0, 0.0), 2.0, difflight.clone())));
+	world.add(Arc::new(Quad::new(Point3::new(3.0, 1.0, -2.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), difflight)));
+
+	let mut cam = Camera::new(16.0/9.0, 400);
+	cam.samples_per_pixel = 100;
+	cam.max_depth = 50;
+	cam.background = Background::Solid(Color::new(0.0, 0.0, 0.0));
+	cam.fov = 20.0;
+	cam.look_from = Point3::new(26.0, 3.0, 6.0);
+	cam.look_at = Point3::new(0.0, 2.0, 0.0);
+	cam.defocus_angle = 0.0;
+
+	Scene { world, camera: cam }
+}
+
+pub fn cornell_box() -> Scene {
+	let mut world = HittableList::default();
+
+	let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+	let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+	let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+	let light = Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
+
+	world.add(Arc::new(Quad::new(Point3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green)));
+	world.add(Arc::new(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red)));
+	world.add(Arc::new(Quad::new(Point3::new(343.0, 554.0, 332.0), Vec3::new(-130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -105.0), light)));
+	world.add(Arc::new(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone())));
+	world.add(Arc::new(Quad::new(Point3::new(555.0, 555.0, 555.0), Vec3::new(-555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -555.0), white.clone())));
+	world.add(Arc::new(Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white.clone())));
+
+	let box1 = Arc::new(make_box(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 330.0, 165.0), white.clone()));
+	world.add(Arc::new(Instance::new(box1, Mat4::translation(Vec3::new(265.0, 0.0, 295.0)) * Mat4::rotation_y(15.0))));
+	let box2 = Arc::new(make_box(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 165.0, 165.0), white));
+	world.add(Arc::new(Instance::new(box2, Mat4::translation(Vec3::new(130.0, 0.0, 65.0)) * Mat4::rotation_y(-18.0))));
+
+	let mut cam = Camera::new(1.0, 600);
+	cam.samples_per_pixel = 200;
+	cam.max_depth = 50;
+	cam.background = Background::Solid(Color::new(0.0, 0.0, 0.0));
+	cam.fov = 40.0;
+	cam.look_from = Point3::new(278.0, 278.0, -800.0);
+	cam.look_at = Point3::new(278.0, 278.0, 0.0);
+	cam.defocus_angle = 0.0;
+
+	Scene { world, camera: cam }
+}
+
+// The Cornell box with its blocks turned into smoke
+pub fn cornell_smoke() -> Scene {
+	let mut world = HittableList::default();
+
+	let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+	let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+	let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+	let light = Arc::new(DiffuseLight::new(Color::new(7.0, 7.0, 7.0)));
+
+	world.add(Arc::new(Quad::new(Point3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green)));
+	world.add(Arc::new(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red)));
+	world.add(Arc::new(Quad::new(Point3::new(113.0, 554.0, 127.0), Vec3::new(330.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 305.0), light)));
+	world.add(Arc::new(Quad::new(Point3::new(0.0, 555.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone())));
+	world.add(Arc::new(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone())));
+	world.add(Arc::new(Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white.clone())));
+
+	let box1 = Arc::new(make_box(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 330.0, 165.0), white.clone()));
+	let box1 = Arc::new(Instance::new(box1, Mat4::translation(Vec3::new(265.0, 0.0, 295.0)) * Mat4::rotation_y(15.0)));
+	let box2 = Arc::new(make_box(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 165.0, 165.0), white));
+	let box2 = Arc::new(Instance::new(box2, Mat4::translation(Vec3::new(130.0, 0.0, 65.0)) * Mat4::rotation_y(-18.0)));
+	world.add(Arc::new(ConstantMedium::from_color(box1, 0.01, Color::new(0.0, 0.0, 0.0))));
+	world.add(Arc::new(ConstantMedium::from_color(box2, 0.01, Color::new(1.0, 1.0, 1.0))));
+
+	let mut cam = Camera::new(1.0, 600);
+	cam.samples_per_pixel = 200;
+	cam.max_depth = 50;
+	cam.background = Background::Solid(Color::new(0.0, 0.0, 0.0));
+	cam.fov = 40.0;
+	cam.look_from = Point3::new(278.0, 278.0, -800.0);
+	cam.look_at = Point3::new(278.0, 278.0, 0.0);
+	cam.defocus_angle = 0.0;
+
+	Scene { world, camera: cam }
+}
+
+// The cover of "Ray Tracing: The Next Week", with a bit of everything from the book
+pub fn final_scene<P: AsRef<Path>>(earth_map: P) -> io::Result<Scene> {
+	let mut boxes1 = HittableList::default();
+	let ground = Arc::new(Lambertian::new(Color::new(0.48, 0.83, 0.53)));
+	let boxes_per_side = 20;
+	for i in 0..boxes_per_side {
+		for j in 0..boxes_per_side {
+			let w = 100.0;
+			let x0 = -1000.0 + i as f64 * w;
+			let z0 = -1000.0 + j as f64 * w;
+			let y1 = random_range(1.0..101.0);
+			boxes1.add(Arc::new(make_box(Point3::new(x0, 0.0, z0), Point3::new(x0 + w, y1, z0 + w), ground.clone())));
+		}
+	}
+
+	let mut world = HittableList::default();
+	world.add(Arc::new(BvhNode::new(boxes1)));
+
+	let light = Arc::new(DiffuseLight::new(Color::new(7.0, 7.0, 7.0)));
+	world.add(Arc::new(Quad::new(Point3::new(123.0, 554.0, 147.0), Vec3::new(300.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 265.0), light)));
+
+	let center1 = Point3::new(400.0, 400.0, 200.0);
+	let center2 = center1 + Vec3::new(30.0, 0.0, 0.0);
+	let sphere_material = Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.1)));
+	world.add(Arc::new(Sphere::moving(center1, center2, 50.0, sphere_material)));
+
+	world.add(Arc::new(Sphere::stationary(Point3::new(260.0, 150.0, 45.0), 50.0, Arc::new(Dielectric { refraction_index: 1.5 }))));
+	world.add(Arc::new(Sphere::stationary(Point3::new(0.0, 150.0, 145.0), 50.0, Arc::new(Metal::new(Color::new(0.8, 0.8, 0.9), 1.0)))));
+
+	// Glass ball filled with blue smoke, and thin mist over everything
+	let boundary = Arc::new(Sphere::stationary(Point3::new(360.0, 150.0, 145.0), 70.0, Arc::new(Dielectric { refraction_index: 1.5 })));
+	world.add(boundary.clone());
+	world.add(Arc::new(ConstantMedium::from_color(boundary, 0.2, Color::new(0.2, 0.4, 0.9))));
+	let boundary = Arc::new(Sphere::stationary(Point3::new(0.0, 0.0, 0.0), 5000.0, Arc::new(Dielectric { refraction_index: 1.5 })));
+	world.add(Arc::new(ConstantMedium::from_color(boundary, 0.0001, Color::new(1.0, 1.0, 1.0))));
+
+	let earth_texture = load_earth_map(earth_map)?;
+	let emat = Arc::new(Lambertian::from_texture(Arc::new(earth_texture)));
+	world.add(Arc::new(Sphere::stationary(Point3::new(400.0, 200.0, 400.0), 100.0, emat)));
+	let pertext = Arc::new(NoiseTexture::new(0, NoisePattern::Marble, 0.2));
+	world.add(Arc::new(Sphere::stationary(Point3::new(220.0, 280.0, 300.0), 80.0, Arc::new(Lambertian::from_texture(pertext)))));
+
+	let mut boxes2 = HittableList::default();
+	let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+	for _ in 0..1000 {
+		boxes2.add(Arc::new(Sphere::stationary(Vec3::random_range(0.0..165.0), 10.0, white.clone())));
+	}
+	let boxes2 = Arc::new(BvhNode::new(boxes2));
+	world.add(Arc::new(Instance::new(boxes2, Mat4::translation(Vec3::new(-100.0, 270.0, 395.0)) * Mat4::rotation_y(15.0))));
+
+	let mut cam = Camera::new(1.0, 400);
+	cam.samples_per_pixel = 250;
+	cam.max_depth = 4;
+	cam.background = Background::Solid(Color::new(0.0, 0.0, 0.0));
+	cam.fov = 40.0;
+	cam.look_from = Point3::new(478.0, 278.0, -600.0);
+	cam.look_at = Point3::new(278.0, 278.0, 0.0);
+	cam.defocus_angle = 0.0;
+
+	Ok(Scene { world, camera: cam })
+}
@@ -0,0 +1,402 @@
+use std::sync::Arc;
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, ray::Ray, sphere::Sphere, vec3::{dot, unit_vector, Point3, Vec3}};
+
+// Signed distance to a surface, negative inside. It may underestimate but never
+// overestimate, or sphere tracing steps through the surface.
+pub trait Sdf: Send + Sync {
+	fn distance(&self, p: Point3) -> f64;
+	// Has to enclose everything where the distance is below zero
+	fn bounding_box(&self) -> Aabb;
+}
+
+// Any closure as a distance function, with a box around its surface
+pub struct FnSdf<F> {
+	f: F,
+	bbox: Aabb,
+}
+
+impl<F: Fn(Point3) -> f64 + Send + Sync> FnSdf<F> {
+	pub fn new(f: F, bbox: Aabb) -> Self {
+		FnSdf { f, bbox }
+	}
+}
+
+impl<F: Fn(Point3) -> f64 + Send + Sync> Sdf for FnSdf<F> {
+	fn distance(&self, p: Point3) -> f64 {
+		(self.f)(p)
+	}
+	fn bounding_box(&self) -> Aabb {
+		self.bbox
+	}
+}
+
+pub struct SphereSdf {
+	pub center: Point3,
+	pub radius: f64,
+}
+
+impl SphereSdf {
+	pub fn new(center: Point3, radius: f64) -> Self {
+		SphereSdf { center, radius }
+	}
+}
+
+impl Sdf for SphereSdf {
+	fn distance(&self, p: Point3) -> f64 {
+		(p - self.center).length() - self.radius
+	}
+	fn bounding_box(&self) -> Aabb {
+		let r = Vec3::new(self.radius, self.radius, self.radius);
+		Aabb::from_points(self.center - r, self.center + r)
+	}
+}
+
+// Axis aligned, half_size from the center to a face on each axis
+pub struct BoxSdf {
+	pub center: Point3,
+	pub half_size: Vec3,
+}
+
+impl BoxSdf {
+	pub fn new(center: Point3, half_size: Vec3) -> Self {
+		BoxSdf { center, half_size }
+	}
+}
+
+impl Sdf for BoxSdf {
+	fn distance(&self, p: Point3) -> f64 {
+		box_distance(p - self.center, self.half_size)
+	}
+	fn bounding_box(&self) -> Aabb {
+		Aabb::from_points(self.center - self.half_size, self.center + self.half_size)
+	}
+}
+
+// A box of the same outer size with its edges and corners rounded off by radius
+pub struct RoundedBoxSdf {
+	pub center: Point3,
+	pub half_size: Vec3,
+	pub radius: f64,
+}
+
+impl RoundedBoxSdf {
+	pub fn new(center: Point3, half_size: Vec3, radius: f64) -> Self {
+		RoundedBoxSdf { center, half_size, radius }
+	}
+}
+
+impl Sdf for RoundedBoxSdf {
+	fn distance(&self, p: Point3) -> f64 {
+		let r = self.radius.min(self.half_size.x()).min(self.half_size.y()).min(self.half_size.z()).max(0.0);
+		box_distance(p - self.center, self.half_size - Vec3::new(r, r, r)) - r
+	}
+	fn bounding_box(&self) -> Aabb {
+		Aabb::from_points(self.center - self.half_size, self.center + self.half_size)
+	}
+}
+
+// Ring around the y axis, major_radius out to the middle of its tube
+pub struct TorusSdf {
+	pub center: Point3,
+	pub major_radius: f64,
+	pub minor_radius: f64,
+}
+
+impl TorusSdf {
+	pub fn new(center: Point3, major_radius: f64, minor_radius: f64) -> Self {
+		TorusSdf { center, major_radius, minor_radius }
+	}
+}
+
+impl Sdf for TorusSdf {
+	fn distance(&self, p: Point3) -> f64 {
+		let q = p - self.center;
+		let ring = (q.x() * q.x() + q.z() * q.z()).sqrt() - self.major_radius;
+		(ring * ring + q.y() * q.y()).sqrt() - self.minor_radius
+	}
+	fn bounding_box(&self) -> Aabb {
+		let outer = self.major_radius + self.minor_radius;
+		let half = Vec3::new(outer, self.minor_radius, outer);
+		Aabb::from_points(self.center - half, self.center + half)
+	}
+}
+
+// Every point within radius of the segment from a to b
+pub struct CapsuleSdf {
+	pub a: Point3,
+	pub b: Point3,
+	pub radius: f64,
+}
+
+impl CapsuleSdf {
+	pub fn new(a: Point3, b: Point3, radius: f64) -> Self {
+		CapsuleSdf { a, b, radius }
+	}
+}
+
+impl Sdf for CapsuleSdf {
+	fn distance(&self, p: Point3) -> f64 {
+		let (pa, ba) = (p - self.a, self.b - self.a);
+		let h = if ba.near_zero() { 0.0 } else { (dot(pa, ba) / ba.length_squared()).clamp(0.0, 1.0) };
+		(pa - h * ba).length() - self.radius
+	}
+	fn bounding_box(&self) -> Aabb {
+		let r = Vec3::new(self.radius, self.radius, self.radius);
+		Aabb::from_boxes(&Aabb::from_points(self.a - r, self.a + r), &Aabb::from_points(self.b - r, self.b + r))
+	}
+}
+
+// Union of two distances that melts them together within about smoothness of where
+// they meet, a plain union at zero
+pub struct SmoothUnion {
+	pub a: Arc<dyn Sdf>,
+	pub b: Arc<dyn Sdf>,
+	pub smoothness: f64,
+}
+
+impl SmoothUnion {
+	pub fn new(a: Arc<dyn Sdf>, b: Arc<dyn Sdf>, smoothness: f64) -> Self {
+		SmoothUnion { a, b, smoothness }
+	}
+}
+
+impl Sdf for SmoothUnion {
+	fn distance(&self, p: Point3) -> f64 {
+		smooth_min(self.a.distance(p), self.b.distance(p), self.smoothness)
+	}
+	fn bounding_box(&self) -> Aabb {
+		// smooth_min is at most a quarter of the smoothness below min, so the blend
+		// bulges out by at most that much on each side
+		let bbox = Aabb::from_boxes(&self.a.bounding_box(), &self.b.bounding_box());
+		let pad = self.smoothness.max(0.0) / 4.0;
+		let grow = |ax: Interval| Interval::new(ax.min - pad, ax.max + pad);
+		Aabb { x: grow(bbox.x), y: grow(bbox.y), z: grow(bbox.z) }
+	}
+}
+
+// Polynomial smooth minimum, k is how far apart a and b can be and still blend
+pub fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+	if k <= 0.0 {
+		return a.min(b);
+	}
+	let h = (k - (a - b).abs()).max(0.0) / k;
+	a.min(b) - h * h * k * 0.25
+}
+
+// Copies of a distance at every multiple of spacing, count of them along each axis
+// starting at the original. Only a bound if each copy fits inside its own cell.
+pub struct Repeat {
+	pub sdf: Arc<dyn Sdf>,
+	pub spacing: Vec3,
+	pub count: [u32; 3],
+}
+
+impl Repeat {
+	pub fn new(sdf: Arc<dyn Sdf>, spacing: Vec3, count: [u32; 3]) -> Self {
+		Repeat { sdf, spacing, count }
+	}
+}
+
+impl Sdf for Repeat {
+	fn distance(&self, p: Point3) -> f64 {
+		let mut q = p;
+		for axis in 0..3 {
+			let spacing = self.spacing[axis];
+			if spacing != 0.0 && self.count[axis] > 1 {
+				let cell = (p[axis] / spacing).round().clamp(0.0, (self.count[axis] - 1) as f64);
+				q[axis] = p[axis] - spacing * cell;
+			}
+		}
+		self.sdf.distance(q)
+	}
+	fn bounding_box(&self) -> Aabb {
+		let bbox = self.sdf.bounding_box();
+		let mut last = Vec3::default();
+		for axis in 0..3 {
+			last[axis] = self.spacing[axis] * self.count[axis].saturating_sub(1) as f64;
+		}
+		let shifted = Aabb {
+			x: Interval::new(bbox.x.min + last.x(), bbox.x.max + last.x()),
+			y: Interval::new(bbox.y.min + last.y(), bbox.y.max + last.y()),
+			z: Interval::new(bbox.z.min + last.z(), bbox.z.max + last.z()),
+		};
+		Aabb::from_boxes(&bbox, &shifted)
+	}
+}
+
+fn box_distance(q: Vec3, half_size: Vec3) -> f64 {
+	let d = Vec3::new(q.x().abs() - half_size.x(), q.y().abs() - half_size.y(), q.z().abs() - half_size.z());
+	let outside = Vec3::new(d.x().max(0.0), d.y().max(0.0), d.z().max(0.0));
+	outside.length() + d.x().max(d.y()).max(d.z()).min(0.0)
+}
+
+// A distance function drawn as a surface by sphere tracing, stepping along the ray by
+// the distance to the surface until it's close enough
+pub struct SdfHittable {
+	sdf: Arc<dyn Sdf>,
+	mat: Arc<dyn Material>,
+	bbox: Aabb,
+	pub max_steps: u32,
+	// How close to the surface counts as a hit, in world units
+	pub epsilon: f64,
+}
+
+impl SdfHittable {
+	pub fn new(sdf: Arc<dyn Sdf>, mat: Arc<dyn Material>) -> Self {
+		let epsilon = 1e-4;
+		let bbox = sdf.bounding_box();
+		// Room for hits that stop just short of the surface
+		let bbox = Aabb { x: bbox.x.expand(4.0 * epsilon), y: bbox.y.expand(4.0 * epsilon), z: bbox.z.expand(4.0 * epsilon) };
+		SdfHittable { sdf, mat, bbox, max_steps: 256, epsilon }
+	}
+	pub fn from_fn<F: Fn(Point3) -> f64 + Send + Sync + 'static>(f: F, bbox: Aabb, mat: Arc<dyn Material>) -> Self {
+		Self::new(Arc::new(FnSdf::new(f, bbox)), mat)
+	}
+
+	// Gradient of the distance from four samples on a tetrahedron
+	fn normal(&self, p: Point3) -> Vec3 {
+		let h = self.epsilon;
+		let mut n = Vec3::default();
+		for k in [Vec3::new(1.0, -1.0, -1.0), Vec3::new(-1.0, -1.0, 1.0), Vec3::new(-1.0, 1.0, -1.0), Vec3::new(1.0, 1.0, 1.0)] {
+			n += k * self.sdf.distance(p + h * k);
+		}
+		if n.near_zero() { Vec3::new(0.0, 1.0, 0.0) } else { unit_vector(&n) }
+	}
+}
+
+impl Hittable for SdfHittable {
+	fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+		let Some(span) = self.bbox.ray_interval(r, ray_t) else {
+			return false;
+		};
+		let ray_length = r.dir().length();
+		let mut t = span.min;
+		let mut d = self.sdf.distance(r.at(t));
+		// Rays leaving the surface start right on it, step off before deciding the side
+		let mut steps = 0;
+		while d.abs() < self.epsilon && steps < 8 {
+			t += 2.0 * self.epsilon / ray_length;
+			d = self.sdf.distance(r.at(t));
+			steps += 1;
+		}
+		// Outside looks for the distance dropping to zero, inside for it rising
+		let side = if d < 0.0 { -1.0 } else { 1.0 };
+		while steps < self.max_steps {
+			if t > span.max {
+				return false;
+			}
+			let step = side * d;
+			if step < self.epsilon {
+				break;
+			}
+			t += step / ray_length;
+			d = self.sdf.distance(r.at(t));
+			steps += 1;
+		}
+		if steps >= self.max_steps || !ray_t.surrounds(t) {
+			return false;
+		}
+
+		rec.t = t;
+		rec.p = r.at(t);
+		let outward_normal = self.normal(rec.p);
+		rec.set_face_normal(r, &outward_normal);
+		(rec.u, rec.v) = Sphere::get_sphere_uv(&outward_normal);
+		rec.vertex_color = None;
+		rec.mat = self.mat.clone();
+		true
+	}
+
+	fn bounding_box(&self) -> Aabb {
+		self.bbox
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::material::DefaultMaterial;
+
+	// Every point of a grid over region that is inside the distance
+	fn inside_points(sdf: &dyn Sdf, min: Point3, max: Point3, step: f64) -> Vec<Point3> {
+		let mut points = Vec::new();
+		let n = |axis: usize| ((max[axis] - min[axis]) / step) as usize;
+		for i in 0..=n(0) {
+			for j in 0..=n(1) {
+				for k in 0..=n(2) {
+					let p = min + step * Vec3::new(i as f64, j as f64, k as f64);
+					if sdf.distance(p) < 0.0 {
+						points.push(p);
+					}
+				}
+			}
+		}
+		points
+	}
+
+	fn contains(bbox: &Aabb, p: Point3) -> bool {
+		bbox.x.contains(p.x()) && bbox.y.contains(p.y()) && bbox.z.contains(p.z())
+	}
+
+	#[test]
+	fn matches_analytic_sphere() {
+		let center = Point3::new(0.5, -0.25, 1.0);
+		let sphere = Sphere::stationary(center, 1.5, Arc::new(DefaultMaterial));
+		let traced = SdfHittable::new(Arc::new(SphereSdf::new(center, 1.5)), Arc::new(DefaultMaterial));
+		let ray_t = Interval::new(0.001, f64::INFINITY);
+		for (origin, dir) in [
+			(Point3::new(0.0, 0.0, 10.0), Vec3::new(0.05, -0.02, -1.0)),
+			(Point3::new(-4.0, 3.0, 2.0), Vec3::new(1.0, -0.8, -0.3)),
+			// Grazing the side, and longer than unit length
+			(Point3::new(1.9, -0.25, -6.0), Vec3::new(0.0, 0.0, 3.0)),
+			(center, Vec3::new(0.3, 0.4, -0.5)),
+		] {
+			let r = Ray::new(origin, dir, 0.0);
+			let (mut expected, mut actual) = (HitRecord::default(), HitRecord::default());
+			assert!(sphere.hit(&r, ray_t, &mut expected));
+			assert!(traced.hit(&r, ray_t, &mut actual));
+			assert!((actual.t - expected.t).abs() * dir.length() < 1e-3, "{} != {}", actual.t, expected.t);
+			assert!((actual.normal - expected.normal).length() < 1e-3, "{} != {}", actual.normal, expected.normal);
+			assert_eq!(actual.front_face, expected.front_face);
+		}
+	}
+
+	#[test]
+	fn exit_from_the_surface() {
+		let traced = SdfHittable::new(Arc::new(SphereSdf::new(Point3::new(0.0, 0.0, 0.0), 1.0)), Arc::new(DefaultMaterial));
+		// Where a refracted ray starts after entering the sphere
+		let r = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+		let mut rec = HitRecord::default();
+		assert!(traced.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+		assert!((rec.t - 2.0).abs() < 1e-3);
+		assert!(!rec.front_face);
+		assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-3);
+	}
+
+	#[test]
+	fn repeat_bounding_box() {
+		let repeat = Repeat::new(Arc::new(SphereSdf::new(Point3::new(0.0, 0.5, 0.0), 0.5)), Vec3::new(2.0, 0.0, -1.5), [3, 4, 2]);
+		let bbox = repeat.bounding_box();
+		let points = inside_points(&repeat, Point3::new(-2.0, -1.0, -4.0), Point3::new(7.0, 2.0, 2.0), 0.1);
+		// Copies along x at 0, 2 and 4, none along y, two along z
+		assert!(points.iter().any(|p| p.x() > 4.0) && points.iter().any(|p| p.z() < -1.5));
+		assert!(points.iter().all(|&p| contains(&bbox, p)));
+	}
+
+	#[test]
+	fn smooth_union_bounding_box() {
+		let a = Arc::new(SphereSdf::new(Point3::new(0.0, 0.0, 0.0), 1.0));
+		// The same sphere twice bulges out the most, by a quarter of the smoothness
+		let same = SmoothUnion::new(a.clone(), a.clone(), 1.0);
+		assert!((same.distance(Point3::new(1.25, 0.0, 0.0))).abs() < 1e-12);
+		let bbox = same.bounding_box();
+		assert!(contains(&bbox, Point3::new(1.25, 0.0, 0.0)) && !contains(&bbox, Point3::new(1.3, 0.0, 0.0)));
+
+		let b = Arc::new(BoxSdf::new(Point3::new(1.5, 0.5, 0.0), Vec3::new(0.5, 0.5, 0.5)));
+		let blend = SmoothUnion::new(a, b, 0.8);
+		let bbox = blend.bounding_box();
+		let points = inside_points(&blend, Point3::new(-2.0, -2.0, -2.0), Point3::new(3.0, 2.0, 2.0), 0.05);
+		assert!(points.iter().all(|&p| contains(&bbox, p)));
+	}
+}
@@ -1,26 +1,48 @@
-use crate::{hittable::{HitRecord, Hittable}, interval::Interval, material::Material, vec3::{dot, Point3, Vec3}};
-use std::rc::Rc;
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, vec3::{dot, Point3, Vec3}};
+use std::{f64::consts::PI, sync::Arc};
 
 pub struct Sphere {
     center1: Point3,
     radius: f64,
-    mat: Rc<dyn Material>,
+    mat: Arc<dyn Material>,
     is_moving: bool,
     center_vec: Vec3,
+    bbox: Aabb,
 }
 
 impl Sphere {
     // Consider something with enums
-    pub fn stationary(center: Point3, radius: f64, mat: Rc<dyn Material>) -> Self {
-        Sphere { center1: center, radius: radius.max(0.0), mat, is_moving: false, center_vec: Default::default() }
+    pub fn stationary(center: Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(radius, radius, radius);
+        let bbox = Aabb::from_points(center - rvec, center + rvec);
+        Sphere { center1: center, radius, mat, is_moving: false, center_vec: Default::default(), bbox }
     }
 
-    pub fn moving(center1: Point3, center2: Point3, radius: f64, mat: Rc<dyn Material>) -> Self {
-        Sphere { center1, radius: radius.max(0.0), mat, is_moving: true, center_vec: center2-center1 }
+    // Moves over times 0 to 1 only, so with another shutter, like the later frames of a
+    // sequence, give the object `MovingInstance` keyframes at those times instead
+    pub fn moving(center1: Point3, center2: Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(radius, radius, radius);
+        // Box has to cover the sphere over the whole time range
+        let box1 = Aabb::from_points(center1 - rvec, center1 + rvec);
+        let box2 = Aabb::from_points(center2 - rvec, center2 + rvec);
+        let bbox = Aabb::from_boxes(&box1, &box2);
+        Sphere { center1, radius, mat, is_moving: true, center_vec: center2-center1, bbox }
     }
 
+    // Moves from center1 at time 0 to center2 at time 1 and holds still outside that,
+    // which keeps it inside its bounding box whatever the shutter
     fn sphere_center(&self, time: f64) -> Point3 {
-        self.center1 + time*self.center_vec
+        self.center1 + time.clamp(0.0, 1.0)*self.center_vec
+    }
+
+    // Maps a point on the unit sphere to (u, v), both in [0, 1]
+    // u goes around the Y axis starting at -X, v from -Y to +Y
+    pub fn get_sphere_uv(p: &Point3) -> (f64, f64) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
     }
 }
 
@@ -52,8 +74,35 @@ impl Hittable for Sphere {
         rec.p = r.at(rec.t);
         let outward_normal = (rec.p - center) / self.radius;
         rec.set_face_normal(r, &outward_normal);
+        (rec.u, rec.v) = Self::get_sphere_uv(&outward_normal);
+        rec.vertex_color = None;
         rec.mat = self.mat.clone();
 
         true
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::DefaultMaterial, ray::Ray};
+
+    fn hit_at(sphere: &Sphere, x: f64, time: f64) -> bool {
+        let r = Ray::new(Point3::new(x, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), time);
+        sphere.hit(&r, Interval::new(0.001, f64::INFINITY), &mut HitRecord::default())
+    }
+
+    #[test]
+    fn moving_holds_outside_unit_time() {
+        let sphere = Sphere::moving(Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 0.0, 0.0), 0.5, Arc::new(DefaultMaterial));
+        assert!(hit_at(&sphere, 1.0, 0.5));
+        // Still at center2 at time 1.5 instead of leaving its bounding box
+        assert!(hit_at(&sphere, 2.0, 1.5) && !hit_at(&sphere, 3.0, 1.5));
+        assert!(hit_at(&sphere, 0.0, -1.0) && !hit_at(&sphere, -2.0, -1.0));
+        assert!(sphere.bounding_box().x.contains(2.5) && !sphere.bounding_box().x.contains(3.0));
+    }
 }
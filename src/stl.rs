@@ -0,0 +1,207 @@
+use std::{collections::HashMap, fmt::Display, fs, io, path::{Path, PathBuf}};
+
+use crate::{mesh::{LoadedMesh, MeshData}, vec3::Point3};
+
+#[derive(Debug)]
+pub enum StlError {
+	Io { path: PathBuf, source: io::Error },
+	Parse { path: PathBuf, message: String },
+}
+
+impl Display for StlError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			StlError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+			StlError::Parse { path, message } => write!(f, "{}: {message}", path.display()),
+		}
+	}
+}
+
+impl std::error::Error for StlError {}
+
+// Reads ASCII or binary STL. Facets are flat, the stored normals are ignored
+// and corners at the same position share a vertex.
+pub fn load_stl<P: AsRef<Path>>(path: P) -> Result<LoadedMesh, StlError> {
+	let path = path.as_ref();
+	let bytes = fs::read(path).map_err(|source| StlError::Io { path: path.to_path_buf(), source })?;
+	parse_stl(&bytes).map_err(|message| StlError::Parse { path: path.to_path_buf(), message })
+}
+
+fn parse_stl(bytes: &[u8]) -> Result<LoadedMesh, String> {
+	// Binary files may start with `solid` too, a matching size or non-text bytes settle it
+	let binary_count = bytes.get(80..84).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+	let is_binary = binary_count.is_some_and(|count| bytes.len() == 84 + 50 * count);
+	match std::str::from_utf8(bytes) {
+		Ok(text) if !is_binary && text.trim_start().starts_with("solid") => parse_ascii(text),
+		_ => parse_binary(bytes),
+	}
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<LoadedMesh, String> {
+	if bytes.len() < 84 {
+		return Err("too short for a binary STL header".to_string());
+	}
+	let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+	if bytes.len() < 84 + 50 * count {
+		return Err(format!("header says {count} triangles but the file only has room for {}", (bytes.len() - 84) / 50));
+	}
+
+	let mut builder = Builder::default();
+	for facet in bytes[84..84 + 50 * count].chunks_exact(50) {
+		let float = |i: usize| f32::from_le_bytes([facet[i], facet[i + 1], facet[i + 2], facet[i + 3]]) as f64;
+		// Skips the normal, then three corners of three floats
+		let corner = |k: usize| Point3::new(float(12 + 12 * k), float(16 + 12 * k), float(20 + 12 * k));
+		builder.add_triangle([corner(0), corner(1), corner(2)]);
+	}
+	Ok(builder.finish())
+}
+
+fn parse_ascii(text: &str) -> Result<LoadedMesh, String> {
+	let mut builder = Builder::default();
+	let mut corners = Vec::with_capacity(3);
+	let mut in_facet = false;
+
+	for (index, line) in text.lines().enumerate() {
+		let line_no = index + 1;
+		let err = |message: String| format!("line {line_no}: {message}");
+		let mut words = line.split_whitespace();
+		match words.next() {
+			None | Some("solid" | "endsolid" | "outer" | "endloop") => {}
+			Some("facet") => {
+				if in_facet {
+					return Err(err("`facet` inside another facet".to_string()));
+				}
+				in_facet = true;
+				corners.clear();
+			}
+			Some("vertex") => {
+				if !in_facet {
+					return Err(err("`vertex` outside a facet".to_string()));
+				}
+				let mut coord = || -> Result<f64, String> {
+					let word = words.next().ok_or_else(|| err("`vertex` needs three coordinates".to_string()))?;
+					word.parse().map_err(|_| err(format!("invalid number `{word}`")))
+				};
+				corners.push(Point3::new(coord()?, coord()?, coord()?));
+			}
+			Some("endfacet") => {
+				if !in_facet {
+					return Err(err("`endfacet` outside a facet".to_string()));
+				}
+				let [a, b, c] = corners[..] else {
+					return Err(err(format!("facet has {} vertices, needs 3", corners.len())));
+				};
+				builder.add_triangle([a, b, c]);
+				corners.clear();
+				in_facet = false;
+			}
+			Some(other) => return Err(err(format!("unknown keyword `{other}`"))),
+		}
+	}
+	if in_facet {
+		return Err("file ends inside a facet".to_string());
+	}
+	Ok(builder.finish())
+}
+
+// Welds corners with bit-identical positions
+#[derive(Default)]
+struct Builder {
+	data: MeshData,
+	triangles: Vec<[u32; 3]>,
+	vertices: HashMap<[u64; 3], u32>,
+}
+
+impl Builder {
+	fn add_triangle(&mut self, corners: [Point3; 3]) {
+		let tri = corners.map(|p| {
+			*self.vertices.entry(p.e.map(f64::to_bits)).or_insert_with(|| {
+				self.data.positions.push(p);
+				(self.data.positions.len() - 1) as u32
+			})
+		});
+		self.triangles.push(tri);
+	}
+
+	fn finish(self) -> LoadedMesh {
+		LoadedMesh { data: self.data, triangles: self.triangles }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const TRIANGLES: [[[f32; 3]; 3]; 2] = [
+		[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+		[[1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+	];
+
+	fn ascii(triangles: &[[[f32; 3]; 3]]) -> String {
+		let mut text = String::from("solid test\n");
+		for tri in triangles {
+			text.push_str("  facet normal 0 0 1\n    outer loop\n");
+			for [x, y, z] in tri {
+				text.push_str(&format!("      vertex {x} {y} {z}\n"));
+			}
+			text.push_str("    endloop\n  endfacet\n");
+		}
+		text.push_str("endsolid test\n");
+		text
+	}
+
+	fn binary(triangles: &[[[f32; 3]; 3]]) -> Vec<u8> {
+		// A header starting with `solid`, like many exporters write
+		let mut bytes = b"solid binary".to_vec();
+		bytes.resize(80, 0);
+		bytes.extend((triangles.len() as u32).to_le_bytes());
+		for tri in triangles {
+			bytes.extend([0.0f32, 0.0, 1.0].iter().flat_map(|f| f.to_le_bytes()));
+			bytes.extend(tri.iter().flatten().flat_map(|f| f.to_le_bytes()));
+			bytes.extend([0, 0]);
+		}
+		bytes
+	}
+
+	fn corners(mesh: &LoadedMesh) -> Vec<[[f64; 3]; 3]> {
+		mesh.triangles.iter().map(|tri| tri.map(|i| mesh.data.positions[i as usize].e)).collect()
+	}
+
+	fn expected() -> Vec<[[f64; 3]; 3]> {
+		TRIANGLES.iter().map(|tri| tri.map(|p| p.map(f64::from))).collect()
+	}
+
+	#[test]
+	fn ascii_round_trip() {
+		let mesh = parse_stl(ascii(&TRIANGLES).as_bytes()).unwrap();
+		assert_eq!(corners(&mesh), expected());
+		// The shared edge is welded
+		assert_eq!(mesh.data.positions.len(), 4);
+	}
+
+	#[test]
+	fn binary_round_trip() {
+		let mesh = parse_stl(&binary(&TRIANGLES)).unwrap();
+		assert_eq!(corners(&mesh), expected());
+		assert_eq!(mesh.data.positions.len(), 4);
+	}
+
+	#[test]
+	fn binary_too_short() {
+		let mut bytes = binary(&TRIANGLES);
+		bytes.truncate(bytes.len() - 10);
+		assert!(parse_binary(&bytes).err().unwrap().contains("header says 2 triangles"));
+	}
+
+	#[test]
+	fn stray_endfacet() {
+		let text = ascii(&TRIANGLES[..1]).replace("endsolid", "endfacet\nendsolid");
+		assert_eq!(parse_stl(text.as_bytes()).err().unwrap(), "line 9: `endfacet` outside a facet");
+	}
+
+	#[test]
+	fn facet_needs_three_vertices() {
+		let text = ascii(&TRIANGLES[..1]).replacen("      vertex 0 1 0\n", "", 1);
+		assert_eq!(parse_stl(text.as_bytes()).err().unwrap(), "line 7: facet has 2 vertices, needs 3");
+	}
+}
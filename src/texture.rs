@@ -0,0 +1,201 @@
+use std::{io, path::Path, sync::Arc};
+
+use crate::{color::{srgb_to_linear, Color}, hittable::HitRecord, image::Image, interval::Interval, perlin::Perlin, vec3::Point3};
+
+pub trait Texture: Send + Sync {
+	fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+	// Lookup for a surface hit, for textures that need more than (u, v, p)
+	fn value_at(&self, rec: &HitRecord) -> Color {
+		self.value(rec.u, rec.v, &rec.p)
+	}
+}
+
+pub struct SolidColor { pub albedo: Color }
+impl SolidColor {
+	pub fn new(albedo: Color) -> Self {
+		SolidColor { albedo }
+	}
+}
+impl Texture for SolidColor {
+	fn value(&self, _: f64, _: f64, _: &Point3) -> Color {
+		self.albedo
+	}
+}
+
+// Colors interpolated from mesh vertices, fallback where the surface has none
+pub struct VertexColorTexture { pub fallback: Color }
+impl VertexColorTexture {
+	pub fn new(fallback: Color) -> Self {
+		VertexColorTexture { fallback }
+	}
+}
+impl Texture for VertexColorTexture {
+	fn value(&self, _: f64, _: f64, _: &Point3) -> Color {
+		self.fallback
+	}
+	fn value_at(&self, rec: &HitRecord) -> Color {
+		rec.vertex_color.unwrap_or(self.fallback)
+	}
+}
+
+// Component-wise product, like a color factor over an image
+pub struct ProductTexture { pub a: Arc<dyn Texture>, pub b: Arc<dyn Texture> }
+impl ProductTexture {
+	pub fn new(a: Arc<dyn Texture>, b: Arc<dyn Texture>) -> Self {
+		ProductTexture { a, b }
+	}
+}
+impl Texture for ProductTexture {
+	fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+		self.a.value(u, v, p) * self.b.value(u, v, p)
+	}
+	fn value_at(&self, rec: &HitRecord) -> Color {
+		self.a.value_at(rec) * self.b.value_at(rec)
+	}
+}
+
+// Alternates between two textures in a 3D grid of cubes
+pub struct CheckerTexture {
+	inv_scale: f64,
+	even: Arc<dyn Texture>,
+	odd: Arc<dyn Texture>,
+}
+impl CheckerTexture {
+	pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+		CheckerTexture { inv_scale: 1.0 / scale, even, odd }
+	}
+	pub fn from_colors(scale: f64, even: Color, odd: Color) -> Self {
+		Self::new(scale, Arc::new(SolidColor::new(even)), Arc::new(SolidColor::new(odd)))
+	}
+}
+impl CheckerTexture {
+	fn pick(&self, p: &Point3) -> &dyn Texture {
+		let x = (self.inv_scale * p.x()).floor() as i64;
+		let y = (self.inv_scale * p.y()).floor() as i64;
+		let z = (self.inv_scale * p.z()).floor() as i64;
+
+		if (x + y + z) % 2 == 0 { self.even.as_ref() } else { self.odd.as_ref() }
+	}
+}
+impl Texture for CheckerTexture {
+	fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+		self.pick(p).value(u, v, p)
+	}
+	fn value_at(&self, rec: &HitRecord) -> Color {
+		self.pick(&rec.p).value_at(rec)
+	}
+}
+
+// Looks up the nearest pixel of an image by surface coordinates
+pub struct ImageTexture {
+	image: Image,
+	// Tile outside [0, 1] instead of clamping to the edge
+	pub repeat: bool,
+}
+impl ImageTexture {
+	pub fn new(image: Image) -> Self {
+		ImageTexture { image, repeat: false }
+	}
+	// Reads a PNG or JPEG, decoding sRGB to linear
+	pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let reader = ::image::ImageReader::open(path)?.with_guessed_format()?;
+		Self::decode(reader)
+	}
+	// Same as load, for images embedded in another file
+	pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+		let reader = ::image::ImageReader::new(io::Cursor::new(bytes)).with_guessed_format()?;
+		Self::decode(reader)
+	}
+	fn decode<R: io::BufRead + io::Seek>(reader: ::image::ImageReader<R>) -> io::Result<Self> {
+		let decoded = reader.decode().map_err(io::Error::other)?.to_rgb32f();
+		let (width, height) = decoded.dimensions();
+		let pixels = decoded.pixels()
+			.map(|p| Color::new(srgb_to_linear(p[0] as f64), srgb_to_linear(p[1] as f64), srgb_to_linear(p[2] as f64)))
+			.collect();
+		Ok(Self::new(Image { width: width as usize, height: height as usize, pixels, aovs: None }))
+	}
+}
+impl Texture for ImageTexture {
+	fn value(&self, u: f64, v: f64, _: &Point3) -> Color {
+		// Cyan makes a missing image easy to spot
+		if self.image.height == 0 || self.image.width == 0 {
+			return Color::new(0.0, 1.0, 1.0);
+		}
+
+		let (u, v) = if self.repeat {
+			(u - u.floor(), v - v.floor())
+		} else {
+			(Interval::new(0.0, 1.0).clamp(u), Interval::new(0.0, 1.0).clamp(v))
+		};
+		// Image rows go top to bottom
+		let v = 1.0 - v;
+
+		let i = ((u * self.image.width as f64) as usize).min(self.image.width - 1);
+		let j = ((v * self.image.height as f64) as usize).min(self.image.height - 1);
+		self.image.get(i, j)
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoisePattern {
+	// Plain noise
+	Noise,
+	Turbulence,
+	Fbm,
+	// Sine stripes along z bent by turbulence
+	Marble,
+	// Rings around the y axis bent by turbulence
+	Wood,
+}
+
+// Blends between two colors by a noise pattern
+pub struct NoiseTexture {
+	noise: Perlin,
+	pub pattern: NoisePattern,
+	pub frequency: f64,
+	pub octaves: u32,
+	pub lacunarity: f64,
+	pub gain: f64,
+	pub low: Color,
+	pub high: Color,
+}
+impl NoiseTexture {
+	pub fn new(seed: u64, pattern: NoisePattern, frequency: f64) -> Self {
+		NoiseTexture {
+			noise: Perlin::new(seed),
+			pattern,
+			frequency,
+			octaves: 7,
+			lacunarity: 2.0,
+			gain: 0.5,
+			low: Color::new(0.0, 0.0, 0.0),
+			high: Color::new(1.0, 1.0, 1.0),
+		}
+	}
+
+	// Pattern value in [0, 1]
+	pub fn pattern_value(&self, p: &Point3) -> f64 {
+		let unit = Interval::new(0.0, 1.0);
+		let scaled = self.frequency * *p;
+		match self.pattern {
+			NoisePattern::Noise => 0.5 * (1.0 + self.noise.noise(&scaled)),
+			NoisePattern::Turbulence => unit.clamp(self.noise.turbulence(&scaled, self.octaves, self.lacunarity, self.gain)),
+			NoisePattern::Fbm => unit.clamp(0.5 * (1.0 + self.noise.fbm(&scaled, self.octaves, self.lacunarity, self.gain))),
+			NoisePattern::Marble => {
+				let turb = self.noise.turbulence(p, self.octaves, self.lacunarity, self.gain);
+				0.5 * (1.0 + (self.frequency * p.z() + 10.0 * turb).sin())
+			}
+			NoisePattern::Wood => {
+				let turb = self.noise.turbulence(p, self.octaves, self.lacunarity, self.gain);
+				let radius = (p.x() * p.x() + p.z() * p.z()).sqrt();
+				(self.frequency * radius + 2.0 * turb).fract()
+			}
+		}
+	}
+}
+impl Texture for NoiseTexture {
+	fn value(&self, _: f64, _: f64, p: &Point3) -> Color {
+		let t = self.pattern_value(p);
+		(1.0 - t) * self.low + t * self.high
+	}
+}
@@ -0,0 +1,134 @@
+use crate::{color::{linear_to_gamma, linear_to_srgb, quantize, Color}, vec3::Vec3};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapOperator {
+	// Leaves the color alone, anything above 1 is clipped
+	Clamp,
+	Reinhard,
+	// Reinhard that maps the white point to 1 instead of infinity
+	ExtendedReinhard { white: f64 },
+	// Stephen Hill's fit of the ACES reference rendering transform
+	Aces,
+	// Minimal AgX with the default contrast curve
+	Agx,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransferFunction {
+	// Square root, what linear_to_gamma does
+	Gamma2,
+	Srgb,
+}
+
+// Turns linear radiance into display values for 8-bit outputs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMapping {
+	pub operator: ToneMapOperator,
+	// In stops, every step doubles the brightness
+	pub exposure: f64,
+	pub transfer: TransferFunction,
+}
+
+impl Default for ToneMapping {
+	fn default() -> Self {
+		Self { operator: ToneMapOperator::Clamp, exposure: 0.0, transfer: TransferFunction::Gamma2 }
+	}
+}
+
+impl ToneMapping {
+	// Linear display color, mostly within [0, 1]
+	pub fn map(&self, pixel_color: Color) -> Color {
+		let c = 2f64.powf(self.exposure) * pixel_color;
+		match self.operator {
+			ToneMapOperator::Clamp => c,
+			ToneMapOperator::Reinhard => scale_luminance(c, |l| l / (1.0 + l)),
+			ToneMapOperator::ExtendedReinhard { white } => {
+				let white_sq = white * white;
+				scale_luminance(c, |l| l * (1.0 + l / white_sq) / (1.0 + l))
+			}
+			ToneMapOperator::Aces => aces(c),
+			ToneMapOperator::Agx => agx(c),
+		}
+	}
+
+	pub fn encode(&self, pixel_color: Color) -> Color {
+		let c = self.map(pixel_color);
+		let transfer = match self.transfer {
+			TransferFunction::Gamma2 => linear_to_gamma,
+			TransferFunction::Srgb => linear_to_srgb,
+		};
+		Color::new(transfer(c.x()), transfer(c.y()), transfer(c.z()))
+	}
+
+	pub fn to_bytes(&self, pixel_color: Color) -> [u8; 3] {
+		quantize(self.encode(pixel_color))
+	}
+}
+
+fn luminance(c: Color) -> f64 {
+	0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+// Maps luminance and keeps the hue
+fn scale_luminance<F: Fn(f64) -> f64>(c: Color, f: F) -> Color {
+	let l = luminance(c);
+	if l <= 0.0 {
+		return Color::default();
+	}
+	f(l) / l * c
+}
+
+fn mat_mul(m: &[[f64; 3]; 3], v: Vec3) -> Vec3 {
+	Vec3::new(
+		m[0][0]*v.x() + m[0][1]*v.y() + m[0][2]*v.z(),
+		m[1][0]*v.x() + m[1][1]*v.y() + m[1][2]*v.z(),
+		m[2][0]*v.x() + m[2][1]*v.y() + m[2][2]*v.z(),
+	)
+}
+
+fn aces(c: Color) -> Color {
+	const INPUT: [[f64; 3]; 3] = [
+		[0.59719, 0.35458, 0.04823],
+		[0.07600, 0.90834, 0.01566],
+		[0.02840, 0.13383, 0.83777],
+	];
+	const OUTPUT: [[f64; 3]; 3] = [
+		[ 1.60475, -0.53108, -0.07367],
+		[-0.10208,  1.10813, -0.00605],
+		[-0.00327, -0.07276,  1.07602],
+	];
+	let rrt_and_odt_fit = |v: f64| (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.4329510) + 0.238081);
+
+	let v = mat_mul(&INPUT, c);
+	let v = Vec3::new(rrt_and_odt_fit(v.x()), rrt_and_odt_fit(v.y()), rrt_and_odt_fit(v.z()));
+	mat_mul(&OUTPUT, v)
+}
+
+fn agx(c: Color) -> Color {
+	const INSET: [[f64; 3]; 3] = [
+		[0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+		[0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+		[0.0423756549057051, 0.0784336, 0.879142973793104],
+	];
+	const OUTSET: [[f64; 3]; 3] = [
+		[1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+		[-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+		[-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+	];
+	const MIN_EV: f64 = -12.47393;
+	const MAX_EV: f64 = 4.026069;
+
+	// Polynomial fit of the default AgX contrast sigmoid, input in log2 space
+	let contrast = |x: f64| {
+		let x = (x.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+		let x2 = x * x;
+		let x4 = x2 * x2;
+		15.5*x4*x2 - 40.14*x4*x + 31.96*x4 - 6.868*x2*x + 0.4298*x2 + 0.1191*x - 0.00232
+	};
+
+	let v = mat_mul(&INSET, c);
+	let v = Vec3::new(contrast(v.x()), contrast(v.y()), contrast(v.z()));
+	let v = mat_mul(&OUTSET, v);
+	// The curve outputs display encoded values, go back to linear
+	Vec3::new(v.x().max(0.0).powf(2.2), v.y().max(0.0).powf(2.2), v.z().max(0.0).powf(2.2))
+}
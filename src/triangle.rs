@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use crate::{aabb::Aabb, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, ray::Ray, vec3::{cross, dot, unit_vector, Point3, Vec3}};
+
+// Single triangle with corners a, b and c, counter-clockwise seen from the front
+pub struct Triangle {
+	a: Point3,
+	// Edges from a to b and from a to c
+	u: Vec3,
+	v: Vec3,
+	w: Vec3,
+	mat: Arc<dyn Material>,
+	bbox: Aabb,
+	normal: Vec3,
+	d: f64,
+}
+
+impl Triangle {
+	pub fn new(a: Point3, b: Point3, c: Point3, mat: Arc<dyn Material>) -> Self {
+		let u = b - a;
+		let v = c - a;
+		let n = cross(u, v);
+		let normal = unit_vector(&n);
+		let d = dot(normal, a);
+		let w = n / dot(n, n);
+
+		let bbox = Aabb::from_boxes(&Aabb::from_points(a, b), &Aabb::from_points(a, c));
+		Triangle { a, u, v, w, mat, bbox, normal, d }
+	}
+}
+
+impl Hittable for Triangle {
+	fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+		let denom = dot(self.normal, *r.dir());
+
+		// Parallel to the plane
+		if denom.abs() < 1e-8 {
+			return false;
+		}
+
+		let t = (self.d - dot(self.normal, *r.origin())) / denom;
+		if !ray_t.contains(t) {
+			return false;
+		}
+
+		let intersection = r.at(t);
+		let planar_hitpt_vector = intersection - self.a;
+		let alpha = dot(self.w, cross(planar_hitpt_vector, self.v));
+		let beta = dot(self.w, cross(self.u, planar_hitpt_vector));
+
+		if alpha < 0.0 || beta < 0.0 || alpha + beta > 1.0 {
+			return false;
+		}
+
+		rec.t = t;
+		rec.p = intersection;
+		// Barycentric weights of b and c
+		(rec.u, rec.v) = (alpha, beta);
+		rec.vertex_color = None;
+		rec.mat = self.mat.clone();
+		rec.set_face_normal(r, &self.normal);
+
+		true
+	}
+
+	fn bounding_box(&self) -> Aabb {
+		self.bbox
+	}
+}
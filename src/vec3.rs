@@ -1,5 +1,7 @@
 use std::{fmt::Display, ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, RangeBounds, Sub}};
-use rand::{distributions::uniform::SampleRange, random, thread_rng, Rng};
+use rand::distributions::uniform::SampleRange;
+
+use crate::rng::{random_double, random_range};
 
 #[derive(Clone, Copy, Default)]
 pub struct Vec3 {
@@ -17,10 +19,9 @@ impl Vec3 {
 	pub fn length(&self) -> f64 {self.length_squared().sqrt()}
 	pub fn length_squared(&self) -> f64 {self.e[0]*self.e[0] + self.e[1]*self.e[1] + self.e[2]*self.e[2]}
 
-	pub fn random() -> Self {Vec3::new(random(), random(), random())}
+	pub fn random() -> Self {Vec3::new(random_double(), random_double(), random_double())}
 	pub fn random_range<T: RangeBounds<f64> + Clone + SampleRange<f64>>(range: T) -> Self {
-		let mut random = thread_rng();
-		Vec3::new(random.gen_range(range.clone()), random.gen_range(range.clone()), random.gen_range(range.clone()))
+		Vec3::new(random_range(range.clone()), random_range(range.clone()), random_range(range.clone()))
 	}
 	pub fn near_zero(&self) -> bool {
 		let s = 1e-8;
@@ -126,6 +127,12 @@ impl DivAssign<f64> for Vec3 {
 }
 
 
+impl From<[f64; 3]> for Vec3 {
+	fn from(e: [f64; 3]) -> Self {
+		Vec3 { e }
+	}
+}
+
 impl Display for Vec3 {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		write!(f, "{} {} {}", self.e[0], self.e[1], self.e[2])
@@ -181,11 +188,20 @@ pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
 }
 
 pub fn random_in_unit_disk() -> Vec3 {
-	let mut rng = thread_rng();
 	loop {
-		let p = Vec3::new(rng.gen_range(-1.0 .. 1.0), rng.gen_range(-1.0 .. 1.0), 0.0);
+		let p = Vec3::new(random_range(-1.0 .. 1.0), random_range(-1.0 .. 1.0), 0.0);
 		if p.length_squared() < 1.0 {
 			return p;
 		}
 	}
-}
\ No newline at end of file
+}
+// Two unit vectors that form a right-handed basis with the unit vector n
+pub fn orthonormal_basis(n: &Vec3) -> (Vec3, Vec3) {
+	let sign = 1.0_f64.copysign(n.z());
+	let a = -1.0 / (sign + n.z());
+	let b = n.x() * n.y() * a;
+	(
+		Vec3::new(1.0 + sign * n.x() * n.x() * a, sign * b, -sign * n.x()),
+		Vec3::new(b, sign + n.y() * n.y() * a, -n.y()),
+	)
+}
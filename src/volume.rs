@@ -0,0 +1,467 @@
+use std::{fmt::Display, fs, io, path::{Path, PathBuf}, sync::Arc};
+
+use crate::{aabb::Aabb, color::Color, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, ray::Ray, rng::random_double, texture::Texture, vec3::{Point3, Vec3}};
+
+#[derive(Debug)]
+pub enum VolumeError {
+	Io { path: PathBuf, source: io::Error },
+	Parse { path: PathBuf, message: String },
+}
+
+impl Display for VolumeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			VolumeError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+			VolumeError::Parse { path, message } => write!(f, "{}: {message}", path.display()),
+		}
+	}
+}
+
+impl std::error::Error for VolumeError {}
+
+// Densities on a regular 3D grid of voxels, x fastest, then y, then z
+pub struct DensityGrid {
+	pub sizes: [usize; 3],
+	data: Vec<f32>,
+	max: f64,
+}
+
+impl DensityGrid {
+	// Panics unless there is one value per voxel. Negative values, from signed files, are
+	// taken as empty space.
+	pub fn new(sizes: [usize; 3], mut data: Vec<f32>) -> Self {
+		assert_eq!(data.len(), sizes.iter().product::<usize>(), "density grid needs one value per voxel");
+		for d in &mut data {
+			*d = d.max(0.0);
+		}
+		let max = data.iter().fold(0.0_f64, |m, &d| m.max(d as f64));
+		DensityGrid { sizes, data, max }
+	}
+
+	// Evaluates f at every voxel center, in coordinates from 0 to 1 across the grid
+	pub fn from_fn<F: Fn(Point3) -> f64>(sizes: [usize; 3], f: F) -> Self {
+		let mut data = Vec::with_capacity(sizes.iter().product());
+		for k in 0..sizes[2] {
+			for j in 0..sizes[1] {
+				for i in 0..sizes[0] {
+					let p = Point3::new(
+						(i as f64 + 0.5) / sizes[0] as f64,
+						(j as f64 + 0.5) / sizes[1] as f64,
+						(k as f64 + 0.5) / sizes[2] as f64,
+					);
+					data.push(f(p).max(0.0) as f32);
+				}
+			}
+		}
+		Self::new(sizes, data)
+	}
+
+	// Bakes a texture, like noise, by the average of its channels. The texture is looked up
+	// at positions in bbox, so noise lines up with the volume it fills.
+	pub fn from_texture(sizes: [usize; 3], tex: &dyn Texture, bbox: &Aabb) -> Self {
+		Self::from_fn(sizes, |p| {
+			let world = Point3::new(bbox.x.min + p.x() * bbox.x.size(), bbox.y.min + p.y() * bbox.y.size(), bbox.z.min + p.z() * bbox.z.size());
+			let c = tex.value(0.0, 0.0, &world);
+			(c.x() + c.y() + c.z()) / 3.0
+		})
+	}
+
+	// Reads NRRD with raw, text or gzip encoding, attached or in a detached data file
+	pub fn load_nrrd<P: AsRef<Path>>(path: P) -> Result<Self, VolumeError> {
+		let path = path.as_ref();
+		let bytes = fs::read(path).map_err(|source| VolumeError::Io { path: path.to_path_buf(), source })?;
+		parse_nrrd(&bytes, path.parent().unwrap_or(Path::new("")))
+			.map_err(|message| VolumeError::Parse { path: path.to_path_buf(), message })
+	}
+
+	// Headerless voxels, as 8-bit values scaled to [0, 1] or little endian 32-bit floats,
+	// told apart by the file size
+	pub fn load_raw<P: AsRef<Path>>(path: P, sizes: [usize; 3]) -> Result<Self, VolumeError> {
+		let path = path.as_ref();
+		let bytes = fs::read(path).map_err(|source| VolumeError::Io { path: path.to_path_buf(), source })?;
+		let count: usize = sizes.iter().product();
+		let data = if bytes.len() == count {
+			bytes.iter().map(|&b| b as f32 / 255.0).collect()
+		} else if bytes.len() == 4 * count {
+			bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+		} else {
+			return Err(VolumeError::Parse {
+				path: path.to_path_buf(),
+				message: format!("{} bytes is neither {count} 8-bit nor {count} float voxels", bytes.len()),
+			});
+		};
+		Ok(Self::new(sizes, data))
+	}
+
+	pub fn max(&self) -> f64 {
+		self.max
+	}
+
+	fn voxel(&self, i: usize, j: usize, k: usize) -> f64 {
+		self.data[(k * self.sizes[1] + j) * self.sizes[0] + i] as f64
+	}
+
+	// Trilinear between voxel centers, p from 0 to 1 across the grid and zero outside
+	pub fn sample(&self, p: &Point3) -> f64 {
+		if (0..3).any(|a| !(0.0..=1.0).contains(&p[a])) || self.data.is_empty() {
+			return 0.0;
+		}
+		let mut lo = [0; 3];
+		let mut hi = [0; 3];
+		let mut frac = [0.0; 3];
+		for a in 0..3 {
+			let x = (p[a] * self.sizes[a] as f64 - 0.5).clamp(0.0, (self.sizes[a] - 1) as f64);
+			lo[a] = x.floor() as usize;
+			hi[a] = (lo[a] + 1).min(self.sizes[a] - 1);
+			frac[a] = x - lo[a] as f64;
+		}
+
+		let mut value = 0.0;
+		for (k, wk) in [(lo[2], 1.0 - frac[2]), (hi[2], frac[2])] {
+			for (j, wj) in [(lo[1], 1.0 - frac[1]), (hi[1], frac[1])] {
+				for (i, wi) in [(lo[0], 1.0 - frac[0]), (hi[0], frac[0])] {
+					value += wi * wj * wk * self.voxel(i, j, k);
+				}
+			}
+		}
+		value
+	}
+}
+
+// Density grid stretched over a box, scattering by its phase function at collisions
+// found with delta tracking against the grid maximum
+pub struct GridVolume {
+	grid: Arc<DensityGrid>,
+	bbox: Aabb,
+	// Extinction per unit length where the grid is 1
+	pub density: f64,
+	phase_function: Arc<dyn Material>,
+}
+
+impl GridVolume {
+	pub fn new(grid: Arc<DensityGrid>, bbox: Aabb, density: f64, phase_function: Arc<dyn Material>) -> Self {
+		GridVolume { grid, bbox, density, phase_function }
+	}
+
+	// Extinction at a point in the box
+	pub fn density_at(&self, p: &Point3) -> f64 {
+		let local = Point3::new(
+			(p.x() - self.bbox.x.min) / self.bbox.x.size(),
+			(p.y() - self.bbox.y.min) / self.bbox.y.size(),
+			(p.z() - self.bbox.z.min) / self.bbox.z.size(),
+		);
+		self.density * self.grid.sample(&local)
+	}
+
+	fn majorant(&self) -> f64 {
+		self.density * self.grid.max()
+	}
+}
+
+impl Hittable for GridVolume {
+	fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+		let Some(inside) = self.bbox.ray_interval(r, ray_t) else {
+			return false;
+		};
+		let majorant = self.majorant();
+		if majorant <= 0.0 {
+			return false;
+		}
+
+		// Tentative collisions against the majorant, real ones in proportion to the density
+		let step = 1.0 / (majorant * r.dir().length());
+		let mut t = inside.min;
+		loop {
+			t -= (1.0 - random_double()).ln() * step;
+			if t >= inside.max {
+				return false;
+			}
+			let p = r.at(t);
+			if random_double() * majorant < self.density_at(&p) {
+				rec.t = t;
+				rec.p = p;
+				// Neither means anything inside a volume
+				rec.normal = Vec3::new(1.0, 0.0, 0.0);
+				rec.front_face = true;
+				rec.u = 0.0;
+				rec.v = 0.0;
+				rec.vertex_color = None;
+				rec.mat = self.phase_function.clone();
+				return true;
+			}
+		}
+	}
+
+	fn bounding_box(&self) -> Aabb {
+		self.bbox
+	}
+}
+
+// A density grid over a box as a color, for emission that follows a temperature grid
+pub struct GridTexture {
+	grid: Arc<DensityGrid>,
+	bbox: Aabb,
+	pub color: Color,
+}
+
+impl GridTexture {
+	pub fn new(grid: Arc<DensityGrid>, bbox: Aabb, color: Color) -> Self {
+		GridTexture { grid, bbox, color }
+	}
+}
+
+impl Texture for GridTexture {
+	fn value(&self, _: f64, _: f64, p: &Point3) -> Color {
+		let local = Point3::new(
+			(p.x() - self.bbox.x.min) / self.bbox.x.size(),
+			(p.y() - self.bbox.y.min) / self.bbox.y.size(),
+			(p.z() - self.bbox.z.min) / self.bbox.z.size(),
+		);
+		self.grid.sample(&local) * self.color
+	}
+}
+
+fn parse_nrrd(bytes: &[u8], base_dir: &Path) -> Result<DensityGrid, String> {
+	if !bytes.starts_with(b"NRRD") {
+		return Err("not a NRRD file".to_string());
+	}
+
+	// Header lines up to the first blank one
+	let mut fields = Vec::new();
+	let mut offset = 0;
+	let mut line_number = 0;
+	loop {
+		let end = bytes[offset..].iter().position(|&b| b == b'\n').map(|i| offset + i);
+		let line = &bytes[offset..end.unwrap_or(bytes.len())];
+		let line = std::str::from_utf8(line).map_err(|_| format!("line {}: header is not text", line_number + 1))?.trim_end_matches('\r');
+		line_number += 1;
+		offset = end.map_or(bytes.len(), |i| i + 1);
+		if line.is_empty() {
+			break;
+		}
+		// Key/value pairs use `:=` and say nothing about the data
+		if line_number > 1 && !line.starts_with('#') && !line.contains(":=") {
+			let (key, value) = line.split_once(": ").ok_or_else(|| format!("line {line_number}: expected `field: value`"))?;
+			fields.push((key.trim().to_ascii_lowercase(), value.trim().to_string()));
+		}
+		if end.is_none() {
+			break;
+		}
+	}
+	let field = |name: &str| fields.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+
+	let dimension = field("dimension").ok_or("missing field `dimension`")?;
+	if dimension != "3" {
+		return Err(format!("dimension {dimension}, only 3D grids are supported"));
+	}
+	let sizes: Vec<usize> = field("sizes").ok_or("missing field `sizes`")?
+		.split_whitespace()
+		.map(|s| s.parse().map_err(|_| format!("invalid size `{s}`")))
+		.collect::<Result<_, _>>()?;
+	let sizes: [usize; 3] = sizes.try_into().map_err(|_| "field `sizes` needs three values".to_string())?;
+
+	let kind = field("type").ok_or("missing field `type`")?;
+	let (width, decode): (usize, fn(&[u8]) -> f64) = match kind {
+		"uchar" | "unsigned char" | "uint8" | "uint8_t" => (1, |b| b[0] as f64),
+		"signed char" | "int8" | "int8_t" => (1, |b| b[0] as i8 as f64),
+		"short" | "short int" | "signed short" | "int16" | "int16_t" => (2, |b| i16::from_le_bytes([b[0], b[1]]) as f64),
+		"ushort" | "unsigned short" | "uint16" | "uint16_t" => (2, |b| u16::from_le_bytes([b[0], b[1]]) as f64),
+		"int" | "signed int" | "int32" | "int32_t" => (4, |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64),
+		"uint" | "unsigned int" | "uint32" | "uint32_t" => (4, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64),
+		"float" => (4, |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64),
+		"double" => (8, |b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])),
+		other => return Err(format!("unsupported type `{other}`")),
+	};
+	let big_endian = match field("endian") {
+		None | Some("little") => false,
+		Some("big") => true,
+		Some(other) => return Err(format!("unknown endian `{other}`")),
+	};
+
+	let detached;
+	let data = match field("data file").or(field("datafile")) {
+		Some(name) => {
+			let path = base_dir.join(name);
+			detached = fs::read(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+			&detached[..]
+		}
+		None => &bytes[offset..],
+	};
+
+	let count: usize = sizes.iter().product();
+	let values: Vec<f32> = match field("encoding").ok_or("missing field `encoding`")? {
+		"raw" => decode_raw(data, count, width, big_endian, decode)?,
+		"gzip" | "gz" => decode_raw(&gunzip(data)?, count, width, big_endian, decode)?,
+		"ascii" | "text" | "txt" => {
+			let text = std::str::from_utf8(data).map_err(|_| "text data is not valid UTF-8".to_string())?;
+			let values: Vec<f32> = text.split_whitespace()
+				.map(|s| s.parse().map_err(|_| format!("invalid value `{s}`")))
+				.collect::<Result<_, _>>()?;
+			if values.len() < count {
+				return Err(format!("{} values for {count} voxels", values.len()));
+			}
+			values[..count].to_vec()
+		}
+		other => return Err(format!("unsupported encoding `{other}`")),
+	};
+	// Integer grids are mapped onto [0, 1] by their largest value
+	let scale = if kind == "float" || kind == "double" { 1.0 } else { values.iter().fold(0.0_f32, |m, &v| m.max(v)).max(1.0) };
+	Ok(DensityGrid::new(sizes, values.iter().map(|v| v / scale).collect()))
+}
+
+fn decode_raw(data: &[u8], count: usize, width: usize, big_endian: bool, decode: fn(&[u8]) -> f64) -> Result<Vec<f32>, String> {
+	if data.len() < count * width {
+		return Err(format!("{} bytes of data for {count} voxels of {width} bytes", data.len()));
+	}
+	Ok(data.chunks_exact(width).take(count).map(|chunk| {
+		if big_endian {
+			let mut swapped = [0; 8];
+			swapped[..width].copy_from_slice(chunk);
+			swapped[..width].reverse();
+			decode(&swapped[..width]) as f32
+		} else {
+			decode(chunk) as f32
+		}
+	}).collect())
+}
+
+// Skips the gzip header and inflates the deflate stream after it
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+	const FHCRC: u8 = 2;
+	const FEXTRA: u8 = 4;
+	const FNAME: u8 = 8;
+	const FCOMMENT: u8 = 16;
+	if data.len() < 18 || data[0] != 0x1f || data[1] != 0x8b || data[2] != 8 {
+		return Err("gzip data has a bad header".to_string());
+	}
+	let flags = data[3];
+	let mut offset = 10;
+	if flags & FEXTRA != 0 {
+		let len = data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize).ok_or("truncated gzip header")?;
+		offset += 2 + len;
+	}
+	for flag in [FNAME, FCOMMENT] {
+		if flags & flag != 0 {
+			offset += data.get(offset..).and_then(|rest| rest.iter().position(|&b| b == 0)).ok_or("truncated gzip header")? + 1;
+		}
+	}
+	if flags & FHCRC != 0 {
+		offset += 2;
+	}
+	let stream = data.get(offset..).ok_or("truncated gzip header")?;
+	miniz_oxide::inflate::decompress_to_vec(stream).map_err(|e| format!("invalid gzip data: {e:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn nrrd(header: &str, data: &[u8]) -> Vec<u8> {
+		let mut bytes = format!("NRRD0004\n# made in memory\n{header}\n").into_bytes();
+		bytes.extend(data);
+		bytes
+	}
+
+	fn parse(bytes: &[u8]) -> Result<DensityGrid, String> {
+		parse_nrrd(bytes, Path::new(""))
+	}
+
+	// The gzip wrapper around a deflate stream, with a file name to skip and no checksum
+	fn gzip(data: &[u8]) -> Vec<u8> {
+		let mut bytes = vec![0x1f, 0x8b, 8, 8, 0, 0, 0, 0, 0, 255];
+		bytes.extend(b"voxels.raw\0");
+		bytes.extend(miniz_oxide::deflate::compress_to_vec(data, 6));
+		bytes.extend([0; 4]);
+		bytes.extend((data.len() as u32).to_le_bytes());
+		bytes
+	}
+
+	const VALUES: [u16; 8] = [0, 100, 200, 300, 400, 500, 600, 800];
+
+	fn values(grid: &DensityGrid) -> Vec<f32> {
+		grid.data.clone()
+	}
+
+	fn expected() -> Vec<f32> {
+		VALUES.iter().map(|&v| v as f32 / 800.0).collect()
+	}
+
+	#[test]
+	fn raw_little_and_big_endian() {
+		let header = |endian: &str| format!("type: ushort\ndimension: 3\nsizes: 2 2 2\nkey:=value\nendian: {endian}\nencoding: raw\n");
+		let little: Vec<u8> = VALUES.iter().flat_map(|v| v.to_le_bytes()).collect();
+		let big: Vec<u8> = VALUES.iter().flat_map(|v| v.to_be_bytes()).collect();
+		let grid = parse(&nrrd(&header("little"), &little)).unwrap();
+		assert_eq!(grid.sizes, [2, 2, 2]);
+		// Integers are scaled by the largest one
+		assert_eq!(values(&grid), expected());
+		assert_eq!(grid.max(), 1.0);
+		assert_eq!(values(&parse(&nrrd(&header("big"), &big)).unwrap()), expected());
+	}
+
+	#[test]
+	fn big_endian_floats_are_not_scaled() {
+		let data: Vec<u8> = [0.5f32, 2.0].iter().flat_map(|v| v.to_be_bytes()).collect();
+		let grid = parse(&nrrd("type: float\ndimension: 3\nsizes: 2 1 1\nendian: big\nencoding: raw\n", &data)).unwrap();
+		assert_eq!(values(&grid), vec![0.5, 2.0]);
+	}
+
+	#[test]
+	fn gzip_encoding() {
+		let data: Vec<u8> = VALUES.iter().flat_map(|v| v.to_le_bytes()).collect();
+		let grid = parse(&nrrd("type: uint16\ndimension: 3\nsizes: 2 2 2\nencoding: gzip\n", &gzip(&data))).unwrap();
+		assert_eq!(values(&grid), expected());
+	}
+
+	#[test]
+	fn ascii_encoding_with_crlf_header() {
+		let bytes = b"NRRD0004\r\ntype: double\r\ndimension: 3\r\nsizes: 2 1 1\r\nencoding: ascii\r\n\r\n0.25\n0.75\n";
+		assert_eq!(values(&parse(bytes).unwrap()), vec![0.25, 0.75]);
+	}
+
+	#[test]
+	fn detached_data_file() {
+		let dir = std::env::temp_dir().join(format!("ray_tracing_nrrd_test_{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(dir.join("grid.raw"), [0u8, 255]).unwrap();
+		let grid = parse_nrrd(&nrrd("type: uchar\ndimension: 3\nsizes: 1 1 2\nencoding: raw\ndata file: grid.raw\n", &[]), &dir);
+		fs::remove_dir_all(&dir).unwrap();
+		assert_eq!(values(&grid.unwrap()), vec![0.0, 1.0]);
+	}
+
+	#[test]
+	fn negative_voxels_are_empty() {
+		let data: Vec<u8> = [-50i16, 100].iter().flat_map(|v| v.to_le_bytes()).collect();
+		let grid = parse(&nrrd("type: short\ndimension: 3\nsizes: 2 1 1\nencoding: raw\n", &data)).unwrap();
+		assert_eq!(values(&grid), vec![0.0, 1.0]);
+	}
+
+	#[test]
+	fn header_errors() {
+		let error = |header: &str, data: &[u8]| parse(&nrrd(header, data)).err().unwrap();
+		assert_eq!(parse(b"P6\n").err().unwrap(), "not a NRRD file");
+		assert_eq!(error("type: uchar\ndimension: 2\nsizes: 2 2\nencoding: raw\n", &[0; 4]), "dimension 2, only 3D grids are supported");
+		assert_eq!(error("type: uchar\ndimension: 3\nencoding: raw\n", &[]), "missing field `sizes`");
+		assert_eq!(error("type: uchar\ndimension: 3\nsizes: 2 2\nencoding: raw\n", &[]), "field `sizes` needs three values");
+		assert_eq!(error("type: block\ndimension: 3\nsizes: 1 1 1\nencoding: raw\n", &[0]), "unsupported type `block`");
+		assert_eq!(error("type: ushort\ndimension: 3\nsizes: 2 1 1\nencoding: raw\n", &[0; 3]), "3 bytes of data for 2 voxels of 2 bytes");
+		assert_eq!(error("type: uchar\ndimension 3\n", &[]), "line 4: expected `field: value`");
+		assert_eq!(error("type: uchar\ndimension: 3\nsizes: 1 1 1\nencoding: gzip\n", &[0; 20]), "gzip data has a bad header");
+	}
+
+	#[test]
+	fn sample_is_trilinear_between_voxel_centers() {
+		// Linear in every axis, so trilinear interpolation gives it back exactly
+		let f = |p: Point3| p.x() + 2.0 * p.y() + 4.0 * p.z();
+		let grid = DensityGrid::from_fn([2, 2, 2], f);
+		// Voxel centers are at 0.25 and 0.75
+		assert_eq!(grid.sample(&Point3::new(0.25, 0.25, 0.25)), f(Point3::new(0.25, 0.25, 0.25)));
+		for p in [Point3::new(0.5, 0.5, 0.5), Point3::new(0.3, 0.6, 0.7), Point3::new(0.75, 0.4, 0.25)] {
+			assert!((grid.sample(&p) - f(p)).abs() < 1e-6);
+		}
+		// Held flat between the outer centers and the edges, zero outside
+		assert_eq!(grid.sample(&Point3::new(0.1, 0.25, 0.25)), grid.sample(&Point3::new(0.25, 0.25, 0.25)));
+		assert_eq!(grid.sample(&Point3::new(1.1, 0.5, 0.5)), 0.0);
+		assert_eq!(grid.sample(&Point3::new(0.5, -0.1, 0.5)), 0.0);
+	}
+}